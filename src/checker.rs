@@ -0,0 +1,1135 @@
+// Importa os tipos definidos no módulo de parser
+use crate::parser::{Cmd, Expr, ExternDecl, FunDecl, Programa, MAX_PARAMETROS_EXTERNA};
+// Importa a avaliação de constantes em tempo de compilação
+use crate::consteval;
+// Importa o número de valores devolvidos pelas funções e as formas das variáveis
+use crate::layout::{forma_inicial, valores_retorno, Forma, Layout};
+// Importa as coleções da biblioteca padrão
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+// Identifica uma variável: label da função onde foi declarada (None para globais) e nome
+type Chave = (Option<String>, String);
+
+// Funções que envolvem o ponto verificado, da mais externa para a mais interna
+type Escopo<'a> = [&'a FunDecl];
+
+// Label do ponto de entrada quando o programa é ligado pela libc, que não pode ser
+// usado como nome de função ou variável global
+const NOME_RESERVADO: &str = "main";
+
+// O que um nome designa em um escopo
+enum Nome<'a> {
+    Variavel(Chave),
+    Constante,
+    Funcao { label: String, aninhada: bool },
+    Externa(&'a ExternDecl),
+    Indefinido,
+}
+
+// Estado de uma global durante a ordenação das inicializações
+#[derive(Clone, Copy, PartialEq)]
+enum Estado {
+    Pendente,
+    EmAndamento,
+    Concluida,
+}
+
+// Verificações semânticas feitas antes da geração de código
+struct Checker<'a> {
+    prog: &'a Programa,
+    funcoes: HashMap<String, &'a FunDecl>,         // Funções por label ("f", "f.g", ...)
+    referencias: HashMap<Chave, BTreeSet<String>>, // Funções que podem estar guardadas em cada variável
+    copias: Vec<(Chave, Chave)>,                   // Variáveis que recebem o valor de outras (destino, origem)
+    enums: HashMap<Chave, &'a str>,                // Enum das variáveis inicializadas com seus valores
+    constantes: HashMap<String, i64>,              // Valores das constantes
+    layout: Layout,                                // Registros e formas das variáveis globais
+}
+
+// Label de uma função: nomes das funções que a envolvem e o seu, separados por '.'
+fn label(escopo: &Escopo) -> String {
+    escopo.iter().map(|f| f.nome.as_str()).collect::<Vec<_>>().join(".")
+}
+
+// Verifica o programa: nomes declarados, número de argumentos das chamadas,
+// inclusive das chamadas indiretas feitas através de variáveis, e dependências
+// entre as inicializações das globais
+pub fn verificar(prog: &Programa) -> Result<(), String> {
+    let checker = Checker::novo(prog)?;
+    checker.verificar_constantes()?;
+    checker.verificar_programa()?;
+    checker.ordenar_globais()?;
+    Ok(())
+}
+
+// Ordem de inicialização das globais (índices em `prog.globais`): cada global vem
+// depois das globais que sua inicialização lê, diretamente ou nas funções chamadas
+pub fn ordem_inicializacao(prog: &Programa) -> Result<Vec<usize>, String> {
+    Checker::novo(prog)?.ordenar_globais()
+}
+
+// Verifica se o programa pode gerar o tipo de saída pedido: executáveis precisam do
+// bloco main; bibliotecas não o têm, então suas globais não podem depender de código
+// de inicialização e as funções públicas recebem os argumentos em registradores
+pub fn verificar_saida(prog: &Programa, biblioteca: bool) -> Result<(), String> {
+    if !biblioteca {
+        return match prog.tem_principal {
+            true => Ok(()),
+            false => Err("Programa sem bloco 'main'".into()),
+        };
+    }
+    if prog.tem_principal {
+        return Err("Bibliotecas não têm bloco 'main'".into());
+    }
+    let constantes = consteval::avaliar_constantes(prog)?;
+    let registros = prog.registros.iter().map(|r| (r.nome.clone(), r.campos.clone())).collect();
+    for (nome, expr) in &prog.globais {
+        if consteval::valor_estatico(expr, &constantes, &registros).is_none() {
+            return Err(format!("Global '{}' de uma biblioteca precisa de valor inicial constante", nome));
+        }
+    }
+    for func in prog.funcoes.iter().filter(|f| f.publica) {
+        if valores_retorno(func) > 1 {
+            return Err(format!("Função pública '{}' não pode devolver uma tupla", func.nome));
+        }
+        if func.parametros.len() > MAX_PARAMETROS_EXTERNA {
+            return Err(format!(
+                "Função pública '{}' com mais de {} parâmetros",
+                func.nome, MAX_PARAMETROS_EXTERNA
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl<'a> Checker<'a> {
+    // Registra as funções e descobre quais funções são guardadas em quais variáveis
+    fn novo(prog: &'a Programa) -> Result<Self, String> {
+        let mut checker = Checker {
+            prog,
+            funcoes: HashMap::new(),
+            referencias: HashMap::new(),
+            copias: Vec::new(),
+            enums: HashMap::new(),
+            constantes: consteval::avaliar_constantes(prog)?,
+            layout: Layout::new(prog),
+        };
+        let globais = prog.funcoes.iter().map(|f| &f.nome).chain(prog.externas.iter().map(|e| &e.nome));
+        let globais = globais.chain(prog.globais.iter().map(|(v, _)| v)).chain(prog.vetores.iter().map(|(v, _)| v));
+        if let Some(nome) = globais.into_iter().find(|nome| *nome == NOME_RESERVADO) {
+            return Err(format!("Nome '{}' é reservado para o ponto de entrada do programa", nome));
+        }
+        for func in &prog.funcoes {
+            checker.registrar_funcao(&[], func)?;
+        }
+        for (i, externa) in prog.externas.iter().enumerate() {
+            let repetida = checker.funcoes.contains_key(&externa.nome)
+                || prog.externas[..i].iter().any(|e| e.nome == externa.nome);
+            if repetida {
+                return Err(format!("Função '{}' declarada mais de uma vez", externa.nome));
+            }
+        }
+        checker.coletar_programa();
+        Ok(checker)
+    }
+
+    // Registra a função e suas funções aninhadas pelo label
+    fn registrar_funcao(&mut self, envolventes: &Escopo<'a>, func: &'a FunDecl) -> Result<(), String> {
+        let escopo = [envolventes, &[func]].concat();
+        if self.funcoes.insert(label(&escopo), func).is_some() {
+            return Err(format!("Função '{}' declarada mais de uma vez", func.nome));
+        }
+        for filha in &func.aninhadas {
+            self.registrar_funcao(&escopo, filha)?;
+        }
+        Ok(())
+    }
+
+    // Resolve um nome da função mais interna para a mais externa; locais escondem globais
+    fn resolver(&self, escopo: &Escopo, nome: &str) -> Nome<'a> {
+        for i in (0..escopo.len()).rev() {
+            let f = escopo[i];
+            let local = f.parametros.iter().any(|p| p == nome)
+                || f.variaveis.iter().any(|(v, _)| v == nome)
+                || f.vetores.iter().any(|(v, _)| v == nome);
+            if local {
+                return Nome::Variavel((Some(label(&escopo[..=i])), nome.to_string()));
+            }
+            if f.aninhadas.iter().any(|g| g.nome == nome) {
+                let label = format!("{}.{}", label(&escopo[..=i]), nome);
+                return Nome::Funcao { label, aninhada: true };
+            }
+        }
+        if self.prog.constantes.iter().any(|(c, _)| c == nome) {
+            return Nome::Constante;
+        }
+        let global = self.prog.globais.iter().any(|(v, _)| v == nome)
+            || self.prog.vetores.iter().any(|(v, _)| v == nome);
+        if global {
+            Nome::Variavel((None, nome.to_string()))
+        } else if self.funcoes.contains_key(nome) {
+            Nome::Funcao { label: nome.to_string(), aninhada: false }
+        } else if let Some(externa) = self.prog.externas.iter().find(|e| e.nome == nome) {
+            Nome::Externa(externa)
+        } else {
+            Nome::Indefinido
+        }
+    }
+
+    // Confere que os nomes das constantes não são usados por globais ou funções
+    fn verificar_constantes(&self) -> Result<(), String> {
+        for (nome, _) in &self.prog.constantes {
+            let repetido = self.prog.globais.iter().any(|(v, _)| v == nome)
+                || self.prog.vetores.iter().any(|(v, _)| v == nome)
+                || self.funcoes.contains_key(nome);
+            if repetido {
+                return Err(format!("Nome '{}' declarado como constante e como variável ou função", nome));
+            }
+        }
+        Ok(())
+    }
+
+    // Registra que a variável pode receber o valor da expressão, se ela for uma função
+    // ou outra variável, cujas funções são repassadas ao fim da coleta
+    fn registrar(&mut self, chave: Chave, expr: &Expr, escopo: &Escopo) {
+        if let Expr::Var(nome) = expr {
+            match self.resolver(escopo, nome) {
+                Nome::Funcao { label, aninhada: false } => {
+                    self.referencias.entry(chave).or_default().insert(label);
+                }
+                Nome::Variavel(origem) if origem != chave => self.copias.push((chave, origem)),
+                _ => {}
+            }
+        }
+    }
+
+    // Repassa as funções de cada variável às que recebem o seu valor, até nada mudar,
+    // para que `var h = g;` possa guardar as mesmas funções que `g`
+    fn propagar_referencias(&mut self) {
+        let mut mudou = true;
+        while mudou {
+            mudou = false;
+            for (destino, origem) in &self.copias {
+                let funcoes = self.referencias.get(origem).cloned().unwrap_or_default();
+                let guardadas = self.referencias.entry(destino.clone()).or_default();
+                for funcao in funcoes {
+                    mudou |= guardadas.insert(funcao);
+                }
+            }
+        }
+    }
+
+    // Uma variável cujo valor inicial é de um enum passa a ser desse enum; as
+    // declarações são visitadas em ordem, então `var d = c;` herda o enum de `c`
+    fn registrar_enum(&mut self, chave: Chave, expr: &Expr, escopo: &Escopo) {
+        if let Some(nome) = self.enum_da_expr(expr, escopo) {
+            self.enums.insert(chave, nome);
+        }
+    }
+
+    // Primeira passada: descobre quais funções são guardadas em quais variáveis
+    // e de quais enums são as variáveis
+    fn coletar_programa(&mut self) {
+        let prog = self.prog;
+        for (nome, expr) in &prog.globais {
+            self.registrar((None, nome.clone()), expr, &[]);
+            self.registrar_enum((None, nome.clone()), expr, &[]);
+            self.coletar_expr(expr, &[]);
+        }
+        for func in &prog.funcoes {
+            self.coletar_funcao(&[func]);
+        }
+        for cmd in &prog.principal {
+            self.coletar_cmd(cmd, &[]);
+        }
+        self.coletar_expr(&prog.retorno, &[]);
+        self.propagar_referencias();
+    }
+
+    fn coletar_funcao(&mut self, escopo: &Escopo<'a>) {
+        let func = escopo[escopo.len() - 1];
+        for (nome, expr) in &func.variaveis {
+            self.registrar((Some(label(escopo)), nome.clone()), expr, escopo);
+            self.registrar_enum((Some(label(escopo)), nome.clone()), expr, escopo);
+            self.coletar_expr(expr, escopo);
+        }
+        for filha in &func.aninhadas {
+            self.coletar_funcao(&[escopo, &[filha]].concat());
+        }
+        for cmd in &func.comandos {
+            self.coletar_cmd(cmd, escopo);
+        }
+        if let Some(retorno) = &func.retorno {
+            self.coletar_expr(retorno, escopo);
+        }
+    }
+
+    fn coletar_cmd(&mut self, cmd: &Cmd, escopo: &Escopo) {
+        if let Cmd::Atrib { nome, expr } = cmd {
+            if let Nome::Variavel(chave) = self.resolver(escopo, nome) {
+                self.registrar(chave, expr, escopo);
+            }
+        }
+        for expr in exprs_do_cmd(cmd) {
+            self.coletar_expr(expr, escopo);
+        }
+        for filho in cmds_do_cmd(cmd) {
+            self.coletar_cmd(filho, escopo);
+        }
+    }
+
+    fn coletar_expr(&mut self, expr: &Expr, escopo: &Escopo) {
+        // Argumentos de chamadas diretas chegam aos parâmetros da função chamada
+        if let Expr::Call { nome, args, nomeados, .. } = expr {
+            if let Nome::Funcao { label, .. } = self.resolver(escopo, nome) {
+                let chamada = self.funcoes[&label];
+                let passados = chamada.parametros.iter().zip(args).chain(nomeados.iter().map(|(p, arg)| (p, arg)));
+                for (param, arg) in passados {
+                    self.registrar((Some(label.clone()), param.clone()), arg, escopo);
+                }
+            }
+        }
+        for filho in subexprs(expr) {
+            self.coletar_expr(filho, escopo);
+        }
+    }
+
+    // Segunda passada: verifica nomes e chamadas
+    fn verificar_programa(&self) -> Result<(), String> {
+        let prog = self.prog;
+        for (_, expr) in &prog.globais {
+            self.verificar_expr(expr, &[])?;
+        }
+        for func in &prog.funcoes {
+            self.verificar_funcao(&[func])?;
+        }
+        for cmd in &prog.principal {
+            self.verificar_cmd(cmd, &[])?;
+        }
+        self.verificar_expr(&prog.retorno, &[])
+    }
+
+    fn verificar_funcao(&self, escopo: &Escopo) -> Result<(), String> {
+        let func = escopo[escopo.len() - 1];
+        // Os valores padrão são calculados por quem chama, então precisam ser constantes
+        for param in func.parametros.iter().filter(|p| func.padroes.contains_key(*p)) {
+            consteval::avaliar(&func.padroes[param], &self.constantes)
+                .map_err(|e| format!("Valor padrão do parâmetro '{}' de '{}': {}", param, func.nome, e))?;
+        }
+        for (_, expr) in &func.variaveis {
+            self.verificar_expr(expr, escopo)?;
+        }
+        for filha in &func.aninhadas {
+            self.verificar_funcao(&[escopo, &[filha]].concat())?;
+        }
+        for cmd in &func.comandos {
+            self.verificar_cmd(cmd, escopo)?;
+        }
+        // Uma função que devolve valor não pode chegar ao fim do corpo sem um return
+        if func.retorno.is_none() && valores_retorno(func) > 0 && !termina(&func.comandos) {
+            return Err(format!("Função '{}' pode chegar ao fim sem devolver valor", func.nome));
+        }
+        match &func.retorno {
+            Some(Expr::Tupla(valores)) => valores.iter().try_for_each(|valor| self.verificar_expr(valor, escopo)),
+            Some(retorno) => self.verificar_expr(retorno, escopo),
+            None => Ok(()),
+        }
+    }
+
+    fn verificar_cmd(&self, cmd: &Cmd, escopo: &Escopo) -> Result<(), String> {
+        match cmd {
+            Cmd::Atrib { nome, expr } => {
+                self.verificar_variavel(nome, escopo)?;
+                self.verificar_escalar(nome, escopo)?;
+                let onde = format!("A atribuição a '{}'", nome);
+                self.verificar_enums([&Expr::Var(nome.clone()), expr], &onde, escopo)?
+            }
+            Cmd::AtribComposta { alvo: Expr::Var(nome), .. } => {
+                self.verificar_variavel(nome, escopo)?;
+                self.verificar_escalar(nome, escopo)?
+            }
+            Cmd::AtribIndice { nome, .. } | Cmd::AtribComposta { alvo: Expr::Indice { nome, .. }, .. } => {
+                self.verificar_variavel(nome, escopo)?
+            }
+            Cmd::AtribCampo { nome, campo, .. } | Cmd::AtribComposta { alvo: Expr::Campo { nome, campo }, .. } => {
+                self.verificar_campo(nome, campo, escopo)?
+            }
+            // O valor de uma chamada usada como comando é descartado, então procedimentos são aceitos
+            Cmd::Expr(chamada @ Expr::Call { nome, args, nomeados, span }) => {
+                self.verificar_chamada(nome, args, nomeados, true, escopo).map_err(|e| format!("{}: {}", span, e))?;
+                return subexprs(chamada).into_iter().try_for_each(|arg| self.verificar_expr(arg, escopo));
+            }
+            Cmd::Asm { operandos, .. } => {
+                for (_, nome) in operandos {
+                    self.verificar_operando_asm(nome, escopo)?;
+                }
+            }
+            Cmd::Match { valor, bracos, padrao } => {
+                let padroes = bracos.iter().flat_map(|(padroes, _)| padroes);
+                self.verificar_enums([valor].into_iter().chain(padroes), "O match", escopo)?;
+                self.verificar_match(bracos, padrao)?;
+            }
+            // Todos os retornos de uma função devolvem o mesmo número de valores
+            Cmd::Return(expr) => {
+                let esperados = escopo.last().map_or(1, |func| valores_retorno(func));
+                let recebidos = match expr {
+                    Some(Expr::Tupla(valores)) => valores.len(),
+                    Some(_) => 1,
+                    None => 0,
+                };
+                if recebidos != esperados {
+                    return Err(format!("Retorno com {} valor(es), mas a função devolve {}", recebidos, esperados));
+                }
+            }
+            _ => {}
+        }
+        for expr in exprs_do_cmd(cmd) {
+            self.verificar_expr(expr, escopo)?;
+        }
+        for filho in cmds_do_cmd(cmd) {
+            self.verificar_cmd(filho, escopo)?;
+        }
+        Ok(())
+    }
+
+    // Os padrões de um match devem ser constantes distintas, e o braço `_` é obrigatório
+    fn verificar_match(&self, bracos: &[(Vec<Expr>, Vec<Cmd>)], padrao: &Option<Vec<Cmd>>) -> Result<(), String> {
+        let mut valores = BTreeSet::new();
+        for padrao in bracos.iter().flat_map(|(padroes, _)| padroes) {
+            let valor = consteval::avaliar(padrao, &self.constantes).map_err(|e| format!("Padrão do match: {}", e))?;
+            if !valores.insert(valor) {
+                return Err(format!("Valor {} repetido nos padrões do match", valor));
+            }
+        }
+        if padrao.is_none() {
+            return Err("Comando match sem o braço '_' para os demais valores".into());
+        }
+        Ok(())
+    }
+
+    // Enum ao qual pertence o valor da expressão: o de uma variante, o de uma variável
+    // inicializada com um valor do enum ou o de um if cujos dois valores são do mesmo
+    // enum. Operações sobre variantes produzem inteiros comuns.
+    fn enum_da_expr(&self, expr: &Expr, escopo: &Escopo) -> Option<&'a str> {
+        match expr {
+            Expr::Var(nome) => match self.resolver(escopo, nome) {
+                Nome::Constante => self
+                    .prog
+                    .enums
+                    .iter()
+                    .find(|e| e.variantes.iter().any(|(v, _)| v == nome))
+                    .map(|e| e.nome.as_str()),
+                Nome::Variavel(chave) => self.enums.get(&chave).copied(),
+                _ => None,
+            },
+            Expr::Condicional { entao, senao, .. } => {
+                let enum_entao = self.enum_da_expr(entao, escopo)?;
+                (self.enum_da_expr(senao, escopo) == Some(enum_entao)).then_some(enum_entao)
+            }
+            _ => None,
+        }
+    }
+
+    // Valores de enums diferentes não podem ser combinados nem comparados entre si
+    fn verificar_enums<'e>(
+        &self,
+        exprs: impl IntoIterator<Item = &'e Expr>,
+        onde: &str,
+        escopo: &Escopo,
+    ) -> Result<(), String> {
+        let mut primeiro = None;
+        for nome in exprs.into_iter().filter_map(|e| self.enum_da_expr(e, escopo)) {
+            match primeiro {
+                None => primeiro = Some(nome),
+                Some(outro) if outro != nome => {
+                    return Err(format!("{} mistura valores dos enums '{}' e '{}'", onde, outro, nome));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn verificar_expr(&self, expr: &Expr, escopo: &Escopo) -> Result<(), String> {
+        match expr {
+            Expr::OpBin { operador, esq, dir } => {
+                self.verificar_enums([esq.as_ref(), dir.as_ref()], &format!("A operação '{}'", operador), escopo)?
+            }
+            Expr::Condicional { entao, senao, .. } => {
+                self.verificar_enums([entao.as_ref(), senao.as_ref()], "O if", escopo)?
+            }
+            Expr::Var(nome) => match self.resolver(escopo, nome) {
+                Nome::Indefinido => return Err(format!("Variável '{}' não declarada", nome)),
+                // O valor de uma função aninhada não levaria o link estático junto
+                Nome::Funcao { aninhada: true, .. } => {
+                    return Err(format!("Função aninhada '{}' não pode ser usada como valor", nome));
+                }
+                // Funções externas seguem outra convenção de chamada
+                Nome::Externa(_) => {
+                    return Err(format!("Função externa '{}' não pode ser usada como valor", nome));
+                }
+                // Chamadas indiretas devolvem um único valor
+                Nome::Funcao { label, .. } if valores_retorno(self.funcoes[&label]) > 1 => {
+                    return Err(format!("Função '{}' devolve uma tupla e não pode ser usada como valor", nome));
+                }
+                Nome::Variavel(_) => self.verificar_escalar(nome, escopo)?,
+                _ => {}
+            },
+            Expr::Tupla(_) => return Err("Tupla só pode ser usada no retorno de uma função".into()),
+            Expr::Desestruturacao { nomes, chamada } => return self.verificar_desestruturacao(nomes, chamada, escopo),
+            Expr::Indice { nome, .. } => self.verificar_variavel(nome, escopo)?,
+            Expr::Campo { nome, campo } => self.verificar_campo(nome, campo, escopo)?,
+            // Constantes não ocupam memória; o endereço de um registro inteiro é permitido
+            Expr::Endereco(alvo) => {
+                if let Expr::Var(nome) = alvo.as_ref() {
+                    match self.resolver(escopo, nome) {
+                        Nome::Constante => self.verificar_variavel(nome, escopo)?,
+                        Nome::Variavel(_) => return Ok(()),
+                        _ => {}
+                    }
+                }
+            }
+            Expr::Call { nome, args, nomeados, span } => {
+                self.verificar_chamada(nome, args, nomeados, false, escopo).map_err(|e| format!("{}: {}", span, e))?
+            }
+            _ => {}
+        }
+        for filho in subexprs(expr) {
+            self.verificar_expr(filho, escopo)?;
+        }
+        Ok(())
+    }
+
+    // Confere a função chamada e os argumentos de uma chamada; procedimentos, que não
+    // devolvem valor, só podem ser chamados como comando
+    fn verificar_chamada(
+        &self,
+        nome: &str,
+        args: &[Expr],
+        nomeados: &[(String, Expr)],
+        comando: bool,
+        escopo: &Escopo,
+    ) -> Result<(), String> {
+        let direta = matches!(self.resolver(escopo, nome), Nome::Funcao { .. });
+        if !nomeados.is_empty() && !direta {
+            return Err(format!("Argumentos nomeados só podem ser usados em chamadas diretas, e '{}' não é uma função", nome));
+        }
+        match self.resolver(escopo, nome) {
+            // Chamada indireta: confere todas as funções que a variável pode guardar
+            Nome::Variavel(chave) => {
+                for alvo in self.referencias.get(&chave).into_iter().flatten() {
+                    self.verificar_aridade(alvo, args.len())?;
+                    if !comando && valores_retorno(self.funcoes[alvo]) == 0 {
+                        return Err(format!(
+                            "'{}' pode guardar o procedimento '{}', que não devolve valor, e só pode ser chamada como comando",
+                            nome, alvo
+                        ));
+                    }
+                }
+            }
+            Nome::Funcao { label, .. } => {
+                self.verificar_argumentos(&label, args, nomeados)?;
+                let valores = valores_retorno(self.funcoes[&label]);
+                if valores == 0 && !comando {
+                    return Err(format!("Procedimento '{}' não devolve valor e só pode ser chamado como comando", label));
+                }
+                if valores > 1 {
+                    return Err(format!(
+                        "Função '{}' devolve {} valores e só pode ser chamada em uma desestruturação",
+                        label, valores
+                    ));
+                }
+            }
+            Nome::Externa(externa) => self.verificar_externa(externa, args.len())?,
+            Nome::Constante => return Err(format!("Constante '{}' não é uma função", nome)),
+            Nome::Indefinido => return Err(format!("Função '{}' não declarada", nome)),
+        }
+        Ok(())
+    }
+
+    // A desestruturação recebe a tupla devolvida por uma chamada direta, com um nome por valor
+    fn verificar_desestruturacao(&self, nomes: &[String], chamada: &Expr, escopo: &Escopo) -> Result<(), String> {
+        let (nome, args, nomeados, span) = match chamada {
+            Expr::Call { nome, args, nomeados, span } => (nome, args, nomeados, span),
+            _ => return Err("Desestruturação espera a chamada de uma função que devolve uma tupla".into()),
+        };
+        let label = match self.resolver(escopo, nome) {
+            Nome::Funcao { label, .. } => label,
+            _ => return Err(format!("Desestruturação espera uma função que devolve uma tupla, mas '{}' não é", nome)),
+        };
+        self.verificar_argumentos(&label, args, nomeados).map_err(|e| format!("{}: {}", span, e))?;
+        let valores = valores_retorno(self.funcoes[&label]);
+        if valores != nomes.len() {
+            return Err(format!(
+                "Função '{}' devolve {} valor(es), mas a desestruturação espera {}",
+                label,
+                valores,
+                nomes.len()
+            ));
+        }
+        args.iter().try_for_each(|arg| self.verificar_expr(arg, escopo))
+    }
+
+    // Ordena as globais pelas dependências de suas inicializações
+    fn ordenar_globais(&self) -> Result<Vec<usize>, String> {
+        let globais = &self.prog.globais;
+        let indices: HashMap<&str, usize> = globais.iter().enumerate().map(|(i, (nome, _))| (nome.as_str(), i)).collect();
+        let dependencias: Vec<Vec<usize>> = globais
+            .iter()
+            .map(|(_, expr)| {
+                let mut lidas = BTreeSet::new();
+                self.leituras(expr, &[], &mut HashSet::new(), &mut lidas);
+                lidas.iter().filter_map(|nome| indices.get(nome.as_str()).copied()).collect()
+            })
+            .collect();
+
+        let mut estados = vec![Estado::Pendente; globais.len()];
+        let mut ordem = Vec::new();
+        for i in 0..globais.len() {
+            self.visitar_global(i, &dependencias, &mut estados, &mut Vec::new(), &mut ordem)?;
+        }
+        Ok(ordem)
+    }
+
+    // Busca em profundidade: coloca as dependências da global na ordem antes dela
+    fn visitar_global(
+        &self,
+        i: usize,
+        dependencias: &[Vec<usize>],
+        estados: &mut [Estado],
+        caminho: &mut Vec<usize>,
+        ordem: &mut Vec<usize>,
+    ) -> Result<(), String> {
+        if estados[i] == Estado::Concluida {
+            return Ok(());
+        }
+        estados[i] = Estado::EmAndamento;
+        caminho.push(i);
+        for &d in &dependencias[i] {
+            if estados[d] == Estado::EmAndamento {
+                let nome = |j: usize| self.prog.globais[j].0.clone();
+                if d == i {
+                    return Err(format!("Global '{}' usada na sua própria inicialização", nome(i)));
+                }
+                let inicio = caminho.iter().position(|&j| j == d).unwrap();
+                let ciclo: Vec<String> = caminho[inicio..].iter().chain([&d]).map(|&j| nome(j)).collect();
+                return Err(format!("Inicialização cíclica das globais: {}", ciclo.join(" -> ")));
+            }
+            self.visitar_global(d, dependencias, estados, caminho, ordem)?;
+        }
+        caminho.pop();
+        estados[i] = Estado::Concluida;
+        ordem.push(i);
+        Ok(())
+    }
+
+    // Coleta as globais lidas pela expressão, inclusive dentro das funções que ela pode
+    // chamar; `visitadas` guarda os labels das funções já percorridas
+    fn leituras(&self, expr: &Expr, escopo: &Escopo, visitadas: &mut HashSet<String>, lidas: &mut BTreeSet<String>) {
+        let mut chamadas = Vec::new();
+        match expr {
+            Expr::Var(nome) | Expr::Indice { nome, .. } | Expr::Campo { nome, .. } => {
+                if let Nome::Variavel((None, global)) = self.resolver(escopo, nome) {
+                    lidas.insert(global);
+                }
+            }
+            Expr::Call { nome, .. } => match self.resolver(escopo, nome) {
+                // Chamada indireta: lê a variável e pode chamar qualquer função guardada nela
+                Nome::Variavel(chave) => {
+                    chamadas.extend(self.referencias.get(&chave).into_iter().flatten().cloned());
+                    if chave.0.is_none() {
+                        lidas.insert(chave.1);
+                    }
+                }
+                Nome::Funcao { label, .. } => chamadas.push(label),
+                _ => {}
+            },
+            _ => {}
+        }
+        for label in chamadas {
+            if visitadas.insert(label.clone()) {
+                self.leituras_funcao(&label, visitadas, lidas);
+            }
+        }
+        for filho in subexprs(expr) {
+            self.leituras(filho, escopo, visitadas, lidas);
+        }
+    }
+
+    fn leituras_funcao(&self, label: &str, visitadas: &mut HashSet<String>, lidas: &mut BTreeSet<String>) {
+        // Reconstrói o escopo a partir do label: "f.g" é a função g declarada em f
+        let partes: Vec<&str> = label.split('.').collect();
+        let escopo: Vec<&FunDecl> = (1..=partes.len()).map(|n| self.funcoes[&partes[..n].join(".")]).collect();
+        let func = escopo[escopo.len() - 1];
+        let mut exprs: Vec<&Expr> = func.variaveis.iter().map(|(_, expr)| expr).collect();
+        let mut cmds: Vec<&Cmd> = func.comandos.iter().collect();
+        while let Some(cmd) = cmds.pop() {
+            // Atribuir a um elemento lê o ponteiro guardado na variável
+            if let Cmd::AtribIndice { nome, .. } = cmd {
+                if let Nome::Variavel((None, global)) = self.resolver(&escopo, nome) {
+                    lidas.insert(global);
+                }
+            }
+            exprs.extend(exprs_do_cmd(cmd));
+            cmds.extend(cmds_do_cmd(cmd));
+        }
+        exprs.extend(&func.retorno);
+        for expr in exprs {
+            self.leituras(expr, &escopo, visitadas, lidas);
+        }
+    }
+
+    fn verificar_variavel(&self, nome: &str, escopo: &Escopo) -> Result<(), String> {
+        match self.resolver(escopo, nome) {
+            Nome::Variavel(_) => Ok(()),
+            Nome::Constante => Err(format!("Constante '{}' não pode ser alterada nem ter seu endereço usado", nome)),
+            _ => Err(format!("Variável '{}' não declarada", nome)),
+        }
+    }
+
+    // Forma da variável: vetor, registro (pelo valor inicial) ou escalar, como no layout
+    fn forma(&self, (dona, nome): &Chave) -> Forma {
+        let func = match dona {
+            Some(label) => self.funcoes[label],
+            None => return self.layout.globais.get(nome).cloned().unwrap_or(Forma::Escalar),
+        };
+        if let Some((_, n)) = func.vetores.iter().find(|(v, _)| v == nome) {
+            return Forma::Vetor(*n);
+        }
+        func.variaveis.iter().find(|(v, _)| v == nome).map_or(Forma::Escalar, |(_, expr)| forma_inicial(expr))
+    }
+
+    // Um registro ocupa vários quadwords e só é acessado pelos campos ou pelo endereço
+    fn verificar_escalar(&self, nome: &str, escopo: &Escopo) -> Result<(), String> {
+        match self.resolver(escopo, nome) {
+            Nome::Variavel(chave) => match self.forma(&chave) {
+                Forma::Registro(tipo) => Err(format!(
+                    "Registro '{}' do tipo '{}' não pode ser usado como valor; use seus campos ou '&{}'",
+                    nome, tipo, nome
+                )),
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // O acesso a `nome.campo` exige um registro que tenha esse campo
+    fn verificar_campo(&self, nome: &str, campo: &str, escopo: &Escopo) -> Result<(), String> {
+        self.verificar_variavel(nome, escopo)?;
+        let chave = match self.resolver(escopo, nome) {
+            Nome::Variavel(chave) => chave,
+            _ => unreachable!("verificar_variavel aceita só variáveis"),
+        };
+        match self.forma(&chave) {
+            Forma::Registro(tipo) if self.layout.offset_campo(&tipo, campo).is_some() => Ok(()),
+            Forma::Registro(tipo) => Err(format!("Registro '{}' não tem o campo '{}'", tipo, campo)),
+            _ => Err(format!("'{}' não é um registro e não tem o campo '{}'", nome, campo)),
+        }
+    }
+
+    // Operandos de asm são endereços fixos: variáveis da própria função, globais ou
+    // constantes (valores imediatos); as de funções envolventes dependem do link estático
+    fn verificar_operando_asm(&self, nome: &str, escopo: &Escopo) -> Result<(), String> {
+        match self.resolver(escopo, nome) {
+            Nome::Variavel((Some(dono), _)) if dono != label(escopo) => {
+                Err(format!("Variável '{}' de uma função envolvente não pode ser operando de asm", nome))
+            }
+            Nome::Variavel(_) | Nome::Constante => Ok(()),
+            _ => Err(format!("Variável '{}' não declarada", nome)),
+        }
+    }
+
+    // Os argumentos de funções externas vão em registradores, que são no máximo seis
+    fn verificar_externa(&self, externa: &ExternDecl, recebidos: usize) -> Result<(), String> {
+        let esperados = externa.parametros.len();
+        if externa.variadica && recebidos > MAX_PARAMETROS_EXTERNA {
+            return Err(format!(
+                "Função externa '{}' recebe no máximo {} argumentos, mas recebeu {}",
+                externa.nome, MAX_PARAMETROS_EXTERNA, recebidos
+            ));
+        }
+        if recebidos < esperados || (!externa.variadica && recebidos > esperados) {
+            let minimo = if externa.variadica { "pelo menos " } else { "" };
+            return Err(format!(
+                "Função '{}' espera {}{} argumento(s), mas recebeu {}",
+                externa.nome, minimo, esperados, recebidos
+            ));
+        }
+        Ok(())
+    }
+
+    // Argumentos de uma chamada direta: os posicionais preenchem os primeiros parâmetros,
+    // os nomeados os demais, e os parâmetros opcionais que sobram recebem o valor padrão
+    fn verificar_argumentos(&self, label: &str, args: &[Expr], nomeados: &[(String, Expr)]) -> Result<(), String> {
+        let func = self.funcoes[label];
+        let obrigatorios = func.parametros.iter().filter(|p| !func.padroes.contains_key(*p)).count();
+        let total = func.parametros.len();
+        let aridade = |recebidos: usize| match obrigatorios == total {
+            true => format!("Função '{}' espera {} argumento(s), mas recebeu {}", label, total, recebidos),
+            false => format!("Função '{}' espera de {} a {} argumento(s), mas recebeu {}", label, obrigatorios, total, recebidos),
+        };
+        if args.len() > total {
+            return Err(aridade(args.len()));
+        }
+        let mut passados: Vec<&String> = func.parametros[..args.len()].iter().collect();
+        for (nome, _) in nomeados {
+            if !func.parametros.contains(nome) {
+                return Err(format!("Função '{}' não tem o parâmetro '{}'", label, nome));
+            }
+            if passados.contains(&nome) {
+                return Err(format!("Parâmetro '{}' de '{}' recebeu mais de um argumento", nome, label));
+            }
+            passados.push(nome);
+        }
+        let faltando = func.parametros.iter().find(|p| !passados.contains(p) && !func.padroes.contains_key(*p));
+        match faltando {
+            Some(_) if nomeados.is_empty() => Err(aridade(args.len())),
+            Some(param) => Err(format!("Falta o argumento '{}' na chamada de '{}'", param, label)),
+            None => Ok(()),
+        }
+    }
+
+    fn verificar_aridade(&self, label: &str, recebidos: usize) -> Result<(), String> {
+        let esperados = self.funcoes[label].parametros.len();
+        if esperados != recebidos {
+            return Err(format!(
+                "Função '{}' espera {} argumento(s), mas recebeu {}",
+                label, esperados, recebidos
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Subexpressões imediatas de uma expressão
+fn subexprs(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::OpBin { esq, dir, .. } => vec![esq, dir],
+        Expr::Call { args, nomeados, .. } => args.iter().chain(nomeados.iter().map(|(_, arg)| arg)).collect(),
+        Expr::Indice { indice, .. } => vec![indice],
+        Expr::Registro { campos, .. } => campos.iter().map(|(_, e)| e).collect(),
+        Expr::Alloc(e) | Expr::Endereco(e) | Expr::Deref(e) => vec![e],
+        Expr::Tupla(valores) => valores.iter().collect(),
+        Expr::Desestruturacao { chamada, .. } => vec![chamada],
+        Expr::Condicional { cond, entao, senao } => vec![cond, entao, senao],
+        Expr::Const(_) | Expr::Str(_) | Expr::Var(_) | Expr::Read | Expr::Campo { .. } | Expr::Componente(_) => vec![],
+    }
+}
+
+// Expressões que aparecem diretamente em um comando
+fn exprs_do_cmd(cmd: &Cmd) -> Vec<&Expr> {
+    match cmd {
+        Cmd::If { cond, .. } | Cmd::While { cond, .. } | Cmd::DoWhile { cond, .. } => vec![cond],
+        Cmd::Return(Some(Expr::Tupla(valores))) => valores.iter().collect(),
+        Cmd::Return(Some(expr)) | Cmd::Expr(expr) => vec![expr],
+        Cmd::Loop { .. } | Cmd::Break | Cmd::Asm { .. } | Cmd::Return(None) => vec![],
+        Cmd::Atrib { expr, .. } | Cmd::AtribCampo { expr, .. } => vec![expr],
+        Cmd::AtribIndice { indice, expr, .. } => vec![indice, expr],
+        Cmd::AtribDeref { ptr, expr } => vec![ptr, expr],
+        Cmd::AtribComposta { alvo, expr, .. } => vec![alvo, expr],
+        Cmd::Print { args, .. } => args.iter().collect(),
+        Cmd::Free(ptr) => vec![ptr],
+        Cmd::Assert { cond, .. } => vec![cond],
+        Cmd::Match { valor, bracos, .. } => [valor].into_iter().chain(bracos.iter().flat_map(|(padroes, _)| padroes)).collect(),
+    }
+}
+
+// Comandos aninhados em um comando
+fn cmds_do_cmd(cmd: &Cmd) -> Vec<&Cmd> {
+    match cmd {
+        Cmd::If { then_cmds, else_cmds, .. } => then_cmds.iter().chain(else_cmds).collect(),
+        Cmd::While { body, .. } | Cmd::Loop { body } | Cmd::DoWhile { body, .. } => body.iter().collect(),
+        Cmd::Match { bracos, padrao, .. } => bracos.iter().flat_map(|(_, cmds)| cmds).chain(padrao.iter().flatten()).collect(),
+        _ => vec![],
+    }
+}
+
+// Indica se os comandos nunca chegam ao fim: terminam em return, em um loop sem
+// break, em um if com os dois lados terminando ou em um match com `_` cujos braços terminam
+fn termina(cmds: &[Cmd]) -> bool {
+    match cmds.last() {
+        Some(Cmd::Return(_)) => true,
+        Some(Cmd::Loop { body }) => !tem_break(body),
+        Some(Cmd::If { then_cmds, else_cmds, .. }) => termina(then_cmds) && termina(else_cmds),
+        Some(Cmd::Match { bracos, padrao: Some(padrao), .. }) => {
+            bracos.iter().all(|(_, cmds)| termina(cmds)) && termina(padrao)
+        }
+        _ => false,
+    }
+}
+
+// Indica se algum break dos comandos encerra o laço que os contém; os breaks de
+// laços internos encerram esses laços
+fn tem_break(cmds: &[Cmd]) -> bool {
+    cmds.iter().any(|cmd| match cmd {
+        Cmd::Break => true,
+        Cmd::While { .. } | Cmd::Loop { .. } | Cmd::DoWhile { .. } => false,
+        _ => cmds_do_cmd(cmd).into_iter().any(|filho| tem_break(std::slice::from_ref(filho))),
+    })
+}
+
+//////////////
+/// TESTES ///
+//////////////
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    // Faz o parsing e a verificação de um programa
+    fn checar(code: &str) -> Result<(), String> {
+        let mut prog = Parser::new(code).parse_programa().unwrap();
+        crate::tipos::anotar(&mut prog);
+        verificar(&prog)
+    }
+
+    // Testa a verificação do número de argumentos em chamadas diretas
+    #[test]
+    fn test_aridade_direta() {
+        assert!(checar("fun f(a, b) { return a + b; } main { return f(1, 2); }").is_ok());
+        let err = checar("fun f(a, b) { return a + b; } main { return f(1); }").unwrap_err();
+        assert!(err.contains("'f' espera 2"), "{}", err);
+        assert!(checar("main { return g(1); }").is_err());
+    }
+
+    // Testa a verificação de chamadas através de variáveis que guardam funções
+    #[test]
+    fn test_aridade_indireta() {
+        let ok = "fun dobro(x) { return 2 * x; } var f = dobro; main { return f(3); }";
+        assert!(checar(ok).is_ok());
+        let err = "fun dobro(x) { return 2 * x; } var f = 0; main { f = dobro; return f(3, 4); }";
+        assert!(checar(err).is_err());
+        // A função também é conferida quando chega a um parâmetro
+        let param = "fun dobro(x) { return 2 * x; } fun aplica(g) { return g(); } main { return aplica(dobro); }";
+        assert!(checar(param).is_err());
+        // As funções guardadas passam de uma variável para outra, mesmo atribuídas depois
+        let alias = "fun p(x) { return x; } var g = p; var h = g; main { return h(1, 2, 3); }";
+        assert!(checar(alias).is_err());
+        let depois = "fun p(x) { return x; } var g = 0; var h = 0; main { h = g; g = p; return h(1, 2); }";
+        assert!(checar(depois).is_err());
+        assert!(checar("fun p(x) { return x; } var g = p; var h = g; main { return h(1); }").is_ok());
+    }
+
+    // Testa a detecção de variáveis não declaradas
+    #[test]
+    fn test_variavel_nao_declarada() {
+        let err = checar("fun f(a) { return a + b; } main { return f(1); }").unwrap_err();
+        assert_eq!(err, "Variável 'b' não declarada");
+        assert!(checar("main { x = 1; return 0; }").is_err());
+    }
+
+    // Testa os nomes visíveis em funções aninhadas
+    #[test]
+    fn test_aninhadas() {
+        let ok = "fun f(n) { var s = 0; fun soma(x) { s = s + x + n; return s; } return soma(1); } main { return f(2); }";
+        assert!(checar(ok).is_ok());
+        // Funções aninhadas não são visíveis fora de quem as declarou
+        let fora = "fun f(n) { fun g() { return n; } return g(); } main { return g(); }";
+        assert!(checar(fora).is_err());
+        // Nem podem ser guardadas em variáveis
+        let valor = "fun f(n) { fun g() { return n; } var h = g; return h(); } main { return f(1); }";
+        let err = checar(valor).unwrap_err();
+        assert!(err.contains("não pode ser usada como valor"), "{}", err);
+        let aridade = "fun f(n) { fun g(a) { return n; } return g(); } main { return f(1); }";
+        assert!(checar(aridade).unwrap_err().contains("'f.g' espera 1"));
+    }
+
+    // Testa os usos permitidos e proibidos de constantes
+    #[test]
+    fn test_constantes() {
+        assert!(checar("const N = 4; fun f(x) { return x * N; } main { return f(N); }").is_ok());
+        let atrib = checar("const N = 4; main { N = 5; return N; }").unwrap_err();
+        assert!(atrib.contains("não pode ser alterada"), "{}", atrib);
+        assert!(checar("const N = 4; main { return *&N; }").is_err());
+        assert!(checar("const N = 4; main { N += 1; return N; }").unwrap_err().contains("não pode ser alterada"));
+        assert!(checar("var x = 1; const N = x; main { return N; }").is_err());
+        assert!(checar("const N = 1; var N = 2; main { return N; }").is_err());
+    }
+
+    // Testa os ciclos entre inicializações de globais
+    #[test]
+    fn test_ciclo_globais() {
+        let propria = checar("var x = x + 1; main { return x; }").unwrap_err();
+        assert_eq!(propria, "Global 'x' usada na sua própria inicialização");
+        let ciclo = checar("fun f() { return b; } var a = f(); var b = a + 1; main { return a; }").unwrap_err();
+        assert_eq!(ciclo, "Inicialização cíclica das globais: a -> b -> a");
+        // Dependências sem ciclo apenas mudam a ordem
+        let prog = Parser::new("fun f() { return b; } var a = f(); var b = read(); main { return a; }")
+            .parse_programa()
+            .unwrap();
+        assert_eq!(ordem_inicializacao(&prog), Ok(vec![1, 0]));
+    }
+
+    // Testa as verificações do comando match
+    #[test]
+    fn test_match() {
+        let ok = "const N = 3; main { match read() { 1 | 2 => { } N => { } _ => { } } return 0; }";
+        assert!(checar(ok).is_ok());
+        let sem_padrao = checar("main { match 1 { 1 => { } } return 0; }").unwrap_err();
+        assert!(sem_padrao.contains("sem o braço '_'"), "{}", sem_padrao);
+        let repetido = checar("const N = 1; main { match 1 { 1 => { } N => { } _ => { } } return 0; }");
+        assert_eq!(repetido, Err("Valor 1 repetido nos padrões do match".into()));
+        assert!(checar("var x = 1; main { match 1 { x => { } _ => { } } return 0; }").is_err());
+    }
+
+    // Testa as chamadas de funções externas
+    #[test]
+    fn test_externas() {
+        // Com funções externas, o bloco principal vira o `main` chamado pela libc
+        let main = checar("extern fun puts(s); fun main() { return 1; } main { return main(); }");
+        assert_eq!(main, Err("Nome 'main' é reservado para o ponto de entrada do programa".into()));
+        assert!(checar("var main = 0; main { return 0; }").is_err());
+        let ok = "extern fun puts(s); extern fun printf(f, ...); var x = 0; main { x = puts(\"oi\"); x = printf(\"%d %d\", 1, 2); return 0; }";
+        assert!(checar(ok).is_ok());
+        let aridade = checar("extern fun puts(s); main { return puts(); }").unwrap_err();
+        assert_eq!(aridade, "1:35-40: Função 'puts' espera 1 argumento(s), mas recebeu 0");
+        let variadica = checar("extern fun printf(f, ...); main { return printf(); }").unwrap_err();
+        assert_eq!(variadica, "1:42-49: Função 'printf' espera pelo menos 1 argumento(s), mas recebeu 0");
+        assert!(checar("extern fun printf(f, ...); main { return printf(1, 2, 3, 4, 5, 6, 7); }").is_err());
+        assert!(checar("extern fun puts(s); var f = puts; main { return 0; }").is_err());
+    }
+
+    // Testa as restrições de executáveis e bibliotecas
+    #[test]
+    fn test_verificar_saida() {
+        let saida = |code: &str, biblioteca| verificar_saida(&Parser::new(code).parse_programa().unwrap(), biblioteca);
+        assert!(saida("main { return 0; }", false).is_ok());
+        assert_eq!(saida("fun f() { return 1; }", false), Err("Programa sem bloco 'main'".into()));
+        assert_eq!(saida("main { return 0; }", true), Err("Bibliotecas não têm bloco 'main'".into()));
+        assert!(saida("const N = 2; var x = N * 3; pub fun f(a) { return a + x; }", true).is_ok());
+        let dinamica = saida("var x = read(); pub fun f() { return x; }", true).unwrap_err();
+        assert_eq!(dinamica, "Global 'x' de uma biblioteca precisa de valor inicial constante");
+        assert!(saida("pub fun f(a, b, c, d, e, f, g) { return a; }", true).is_err());
+    }
+
+    // Testa os usos permitidos e proibidos de tuplas
+    #[test]
+    fn test_tuplas() {
+        let divmod = "fun divmod(a, b) { return (a / b, a - a / b * b); }";
+        assert!(checar(&format!("{} fun f() {{ var (q, r) = divmod(7, 2); return q + r; }} main {{ return f(); }}", divmod)).is_ok());
+        let nomes = checar(&format!("{} fun f() {{ var (q, r, s) = divmod(7, 2); return q; }} main {{ return f(); }}", divmod));
+        assert_eq!(nomes, Err("Função 'divmod' devolve 2 valor(es), mas a desestruturação espera 3".into()));
+        let expressao = checar(&format!("{} main {{ return divmod(7, 2); }}", divmod)).unwrap_err();
+        assert!(expressao.contains("só pode ser chamada em uma desestruturação"), "{}", expressao);
+        assert!(checar(&format!("{} var g = divmod; main {{ return 0; }}", divmod)).is_err());
+        assert!(checar("main { return (1, 2); }").is_err());
+        assert!(checar("fun f() { return 1; } fun g() { var (a, b) = f(); return a; } main { return g(); }").is_err());
+    }
+
+    // Testa os retornos antecipados
+    #[test]
+    fn test_retornos() {
+        let ok = "fun f(n) { loop { if n > 10 { return (n, 1); } else { } n = n * 2; } return (n, 0); } fun g() { var (a, b) = f(3); return a; } main { return g(); }";
+        assert!(checar(ok).is_ok());
+        let misturado = checar("fun f(n) { if n > 0 { return 1; } else { } return (n, 0); } main { return 0; }");
+        assert_eq!(misturado, Err("Retorno com 1 valor(es), mas a função devolve 2".into()));
+        assert!(checar("main { if 1 { return (1, 2); } else { } return 0; }").is_err());
+    }
+
+    // Testa o uso das variantes e a mistura de enums diferentes
+    #[test]
+    fn test_enums() {
+        let enums = "enum Cor { Vermelho, Verde, Azul } enum Tamanho { Pequeno, Grande }";
+        let ok = format!("{} var c = Verde; main {{ match c {{ Vermelho | Azul => {{ c = Verde; }} _ => {{ }} }} return c == Azul; }}", enums);
+        assert!(checar(&ok).is_ok());
+        let comparacao = checar(&format!("{} main {{ return Verde == Grande; }}", enums));
+        assert_eq!(comparacao, Err("A operação '==' mistura valores dos enums 'Cor' e 'Tamanho'".into()));
+        let padroes = checar(&format!("{} var c = 0; main {{ match c {{ Verde => {{ }} Grande => {{ }} _ => {{ }} }} return 0; }}", enums));
+        assert_eq!(padroes, Err("O match mistura valores dos enums 'Cor' e 'Tamanho'".into()));
+        assert!(checar(&format!("{} main {{ return if 1 {{ Azul }} else {{ Pequeno }}; }}", enums)).is_err());
+        // Variáveis inicializadas com uma variante, direta ou indiretamente, são do seu enum
+        let variavel = checar(&format!("{} var c = Verde; var d = c; main {{ if d == Grande {{ }} else {{ }} return 0; }}", enums));
+        assert_eq!(variavel, Err("A operação '==' mistura valores dos enums 'Cor' e 'Tamanho'".into()));
+        let local = checar(&format!("{} fun f() {{ var t = Pequeno; match t {{ Grande => {{ }} Azul => {{ }} _ => {{ }} }} return 0; }} main {{ return f(); }}", enums));
+        assert_eq!(local, Err("O match mistura valores dos enums 'Tamanho' e 'Cor'".into()));
+        let atribuicao = checar(&format!("{} var c = Verde; main {{ c = Grande; return 0; }}", enums));
+        assert_eq!(atribuicao, Err("A atribuição a 'c' mistura valores dos enums 'Cor' e 'Tamanho'".into()));
+        assert!(checar(&format!("{} var c = Verde; main {{ c = c + 1; return 0; }}", enums)).is_ok());
+        // Variantes de enums diferentes com o mesmo nome seriam constantes repetidas
+        assert!(checar("enum A { X } enum B { X } main { return X; }").is_err());
+    }
+
+    // Testa constantes e padrões u64, avaliados com as operações sem sinal
+    #[test]
+    fn test_constantes_sem_sinal() {
+        assert!(checar("const A = 0x8000_0000_0000_0000 / 0xFFFF_FFFF_FFFF_FFFF; main { return A; }").is_ok());
+        let padroes = "var h: u64 = 0; main { match h { 0x8000_0000_0000_0000 / 0xFFFF_FFFF_FFFF_FFFF => { } 1 => { } _ => { } } return 0; }";
+        assert!(checar(padroes).is_ok());
+        // 0xFFFF_FFFF_FFFF_FFFF >> 1 é i64::MAX no deslocamento lógico, repetindo o outro padrão
+        let repetido = "var h: u64 = 0; main { match h { 0xFFFF_FFFF_FFFF_FFFF >> 1 => { } 0x7FFF_FFFF_FFFF_FFFF => { } _ => { } } return 0; }";
+        assert!(checar(repetido).is_err());
+    }
+
+    // Testa as variáveis aceitas como operandos de asm
+    #[test]
+    fn test_asm() {
+        let ok = r#"const N = 60; var g = 0; fun f(x) { asm { "mov rax, {n}", "add rax, {x}", "mov {g}, rax", n = N, x = x, g = g } return g; } main { return f(1); }"#;
+        assert!(checar(ok).is_ok());
+        let capturada = checar(r#"fun f(x) { fun g() { asm { "inc qword {x}", x = x } return 0; } return g(); } main { return f(1); }"#);
+        assert_eq!(capturada, Err("Variável 'x' de uma função envolvente não pode ser operando de asm".into()));
+        assert!(checar(r#"main { asm { "mov rax, {y}", y = y } return 0; }"#).is_err());
+    }
+
+    // Testa o uso de procedimentos como comandos e a rejeição do seu valor
+    #[test]
+    fn test_procedimentos() {
+        let mostra = "fun mostra(x) { if x < 0 { return; } else { } print(x); }";
+        assert!(checar(&format!("{} fun dobro(x) {{ return 2 * x; }} main {{ mostra(1); dobro(2); return 0; }}", mostra)).is_ok());
+        let valor = checar(&format!("{} main {{ return mostra(1) + 1; }}", mostra));
+        assert_eq!(valor, Err("1:73-81: Procedimento 'mostra' não devolve valor e só pode ser chamado como comando".into()));
+        assert!(checar(&format!("{} main {{ mostra(1, 2); return 0; }}", mostra)).is_err());
+        let misturado = checar("fun f(x) { if x { return 1; } else { } return; } main { return 0; }");
+        assert_eq!(misturado, Err("Retorno com 0 valor(es), mas a função devolve 1".into()));
+        assert!(checar("fun f(c) { loop { if c { return 1; } else { } } } main { return f(1); }").is_ok());
+        let indireta = checar("fun p(x) { print(x); } var g = p; main { return g(1); }").unwrap_err();
+        assert!(indireta.ends_with("'g' pode guardar o procedimento 'p', que não devolve valor, e só pode ser chamada como comando"), "{}", indireta);
+        assert!(checar("fun p(x) { print(x); } var g = p; main { g(1); return 0; }").is_ok());
+        // Uma cópia da variável continua podendo guardar o procedimento
+        assert!(checar("fun p(x) { print(x); } var g = p; var h = g; main { return h(1); }").is_err());
+        // Funções que devolvem valor não podem sair do corpo sem passar por um return
+        let sem_retorno = checar("fun f(c) { if c { return 1; } else { } } main { return f(1); }");
+        assert_eq!(sem_retorno, Err("Função 'f' pode chegar ao fim sem devolver valor".into()));
+        let com_break = checar("fun f(c) { loop { if c { break; } else { return 1; } } } main { return f(1); }");
+        assert!(com_break.is_err());
+        assert!(checar("fun f(c) { if c { return 1; } else { return 2; } } main { return f(1); }").is_ok());
+        assert!(checar("fun f(c) { loop { while c { break; } return 1; } } main { return f(1); }").is_ok());
+        assert!(checar("main { if 1 { return; } else { } return 0; }").is_err());
+    }
+
+    // Testa os argumentos nomeados e os valores padrão, com os erros indicando a chamada
+    #[test]
+    fn test_argumentos_nomeados() {
+        let range = "const UM = 1; fun range(lo, hi, passo = UM) { return (hi - lo) / passo; }";
+        let ok = format!("{} main {{ return range(0, 10) + range(0, 10, 2) + range(hi = 10, lo = 0) + range(0, 10, passo = 5); }}", range);
+        assert!(checar(&ok).is_ok());
+        let nome = checar(&format!("{} main {{ return range(0, 10, step = 2); }}", range));
+        assert_eq!(nome, Err("1:89-110: Função 'range' não tem o parâmetro 'step'".into()));
+        let repetido = checar(&format!("{} main {{ return range(0, 10, hi = 2); }}", range)).unwrap_err();
+        assert!(repetido.ends_with("Parâmetro 'hi' de 'range' recebeu mais de um argumento"), "{}", repetido);
+        let aridade = checar(&format!("{} main {{ return range(0); }}", range)).unwrap_err();
+        assert!(aridade.ends_with("Função 'range' espera de 2 a 3 argumento(s), mas recebeu 1"), "{}", aridade);
+        let faltando = checar(&format!("{} main {{ return range(passo = 2, lo = 0); }}", range)).unwrap_err();
+        assert!(faltando.ends_with("Falta o argumento 'hi' na chamada de 'range'"), "{}", faltando);
+        assert!(checar("var x = 1; fun f(a = x) { return a; } main { return f(); }").is_err());
+        assert!(checar("fun f(a) { return a; } var g = f; main { return g(a = 1); }").is_err());
+    }
+
+    // Testa os acessos a campos e o uso de registros inteiros como valores
+    #[test]
+    fn test_campos_registros() {
+        let point = "struct Point { x, y }";
+        let ok = format!("{} var p = Point {{ x: 1 }}; fun f(q) {{ return q; }} main {{ p.y = 2; p.x += 1; return f(&p) + p.y; }}", point);
+        assert!(checar(&ok).is_ok());
+        let escalar = checar(&format!("{} var x = 1; main {{ return x.y; }}", point));
+        assert_eq!(escalar, Err("'x' não é um registro e não tem o campo 'y'".into()));
+        let local = checar(&format!("{} fun f(a) {{ var b = 0; b.y = a; return a.y; }} main {{ return f(1); }}", point));
+        assert_eq!(local, Err("'b' não é um registro e não tem o campo 'y'".into()));
+        assert!(checar(&format!("{} fun f(a) {{ return a.y; }} main {{ return f(1); }}", point)).is_err());
+        let campo = checar(&format!("{} var p = Point {{ x: 1 }}; main {{ p.z += 1; return 0; }}", point));
+        assert_eq!(campo, Err("Registro 'Point' não tem o campo 'z'".into()));
+        let copia = checar(&format!("{} var p = Point {{ x: 1 }}; var q = 0; main {{ q = p; return 0; }}", point));
+        assert_eq!(copia, Err("Registro 'p' do tipo 'Point' não pode ser usado como valor; use seus campos ou '&p'".into()));
+        let argumento = format!("{} fun f(a) {{ return a; }} fun g() {{ var p = Point {{ x: 1 }}; return f(p); }} main {{ return g(); }}", point);
+        assert!(checar(&argumento).is_err());
+        assert!(checar(&format!("{} var p = Point {{ x: 1 }}; main {{ p = 3; return 0; }}", point)).is_err());
+    }
+}
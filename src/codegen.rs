@@ -1,97 +1,811 @@
 // Importa os tipos definidos no módulo de parser
-use crate::parser::{Expr, Cmd, Programa, FunDecl};
-// Importa o HashMap da biblioteca padrão
-use std::collections::HashMap;
+use crate::parser::{Expr, Cmd, Programa, FunDecl, Tipo};
+// Importa o cálculo de layout das variáveis e registros
+use crate::layout::{Forma, Layout, Local, Quadro, valores_retorno, MAX_VALORES_REGISTRADORES};
+// Importa as rotinas de suporte emitidas junto com o programa
+use crate::runtime::{self, Rotina};
+// Importa a avaliação de constantes em tempo de compilação
+use crate::consteval;
+// Importa a ordenação das inicializações das globais
+use crate::checker;
+// Importa as coleções da biblioteca padrão
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+// Registradores dos argumentos de funções externas, na ordem do System V AMD64 ABI
+const REGISTRADORES_ARGUMENTOS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+
+// Número mínimo de valores para um match usar tabela de saltos
+const MIN_CASOS_TABELA: usize = 4;
+// Número máximo de entradas de uma tabela de saltos
+const MAX_ENTRADAS_TABELA: i128 = 1024;
+
+// Código de saída do programa quando um índice de vetor está fora dos limites
+pub const STATUS_FORA_DOS_LIMITES: i32 = 200;
+// Código de saída do programa quando a condição de um assert é falsa
+pub const STATUS_ASSERCAO_FALHOU: i32 = 201;
+
+// Tipo do arquivo produzido pelo compilador
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TipoSaida {
+    #[default]
+    Executavel,         // Programa com ponto de entrada
+    Objeto,             // Arquivo objeto para ser ligado a código C
+    BibliotecaEstatica, // Arquivo .a com o objeto
+}
+
+// Opções que alteram o código gerado
+#[derive(Debug, Default, Clone)]
+pub struct Opcoes {
+    pub verificar_limites: bool, // Verifica os índices de vetores em tempo de execução
+    pub tipo_saida: TipoSaida,   // Executável ou biblioteca para C
+    pub arquivo: String,         // Nome do arquivo fonte, citado nas falhas de assert
+}
+
+// Estado compartilhado durante a geração de código
+#[derive(Default)]
+struct Contexto {
+    labels: usize,                            // Contador de labels para gerar nomes únicos
+    layout: Layout,                           // Registros e formas das variáveis globais
+    quadro: Quadro,                           // Quadro de pilha da função atual
+    envolventes: Vec<Quadro>,                 // Quadros das funções que envolvem a atual (a mais próxima por último)
+    aninhadas: Vec<HashMap<String, String>>,  // Funções aninhadas de cada nível (nome -> label), a atual por último
+    funcoes: HashSet<String>,                 // Nomes das funções de nível superior
+    parametros: HashMap<String, (Vec<String>, HashMap<String, Expr>)>, // Parâmetros e valores padrão de cada função, pelo label
+    externas: HashSet<String>,                // Nomes das funções externas (C)
+    exportadas: HashSet<String>,              // Funções públicas de bibliotecas, chamadas de C pelo seu nome
+    biblioteca: bool,                         // Gera código independente de posição, sem ponto de entrada
+    usa_libc: bool,                           // Ligado com a libc: chama funções externas ou é uma biblioteca
+    constantes: HashMap<String, i64>,         // Valores das constantes, usados como imediatos
+    verificar_limites: bool,                  // Gera verificação de índices nos acessos a vetores
+    arquivo: String,                          // Nome do arquivo fonte, citado nas falhas de assert
+    usa_assert: bool,                         // Indica se a rotina de falha de assert deve ser emitida
+    lacos: Vec<usize>,                        // Labels de saída (Lfim) dos laços que envolvem o comando atual
+    retorno: Option<usize>,                   // Label do epílogo da função atual, criado no primeiro return antecipado
+    strings: Vec<String>,                     // Literais de string, emitidos em .rodata
+    tabelas: Vec<Vec<String>>,                // Tabelas de salto dos comandos match, emitidas em .rodata ou .data
+    rotinas: BTreeSet<Rotina>,                // Rotinas do runtime usadas pelo programa
+}
 
 // Função principal que gera o código assembly a partir de um programa
-pub fn gerar_codigo(prog: &Programa) -> String {
+pub fn gerar_codigo(prog: &Programa, opcoes: &Opcoes) -> String {
     let mut codigo = String::new(); // Código final a ser construído
-    let mut labels = 0; // Contador de labels para gerar nomes únicos
+    let biblioteca = opcoes.tipo_saida != TipoSaida::Executavel;
+    let mut ctx = Contexto {
+        layout: Layout::new(prog),
+        funcoes: prog.funcoes.iter().map(|f| f.nome.clone()).collect(),
+        parametros: prog.funcoes.iter().map(|f| (f.nome.clone(), (f.parametros.clone(), f.padroes.clone()))).collect(),
+        externas: prog.externas.iter().map(|e| e.nome.clone()).collect(),
+        exportadas: match biblioteca {
+            true => prog.funcoes.iter().filter(|f| f.publica).map(|f| f.nome.clone()).collect(),
+            false => HashSet::new(),
+        },
+        biblioteca,
+        usa_libc: biblioteca || !prog.externas.is_empty(),
+        constantes: consteval::avaliar_constantes(prog).expect("Constantes verificadas antes da geração"),
+        verificar_limites: opcoes.verificar_limites,
+        arquivo: opcoes.arquivo.clone(),
+        ..Default::default()
+    };
+
+    // Bibliotecas podem ser ligadas a executáveis independentes de posição:
+    // os acessos à memória passam a ser relativos a rip
+    if biblioteca {
+        codigo.push_str("default rel\n");
+    }
 
-    // Declaração da seção BSS para alocar espaço para variáveis globais
+    // Globais com valor inicial calculável em tempo de compilação vão para a seção de dados
+    let estaticas: HashMap<&String, Vec<i64>> = prog
+        .globais
+        .iter()
+        .filter_map(|(nome, expr)| {
+            consteval::valor_estatico(expr, &ctx.constantes, &ctx.layout.registros).map(|valores| (nome, valores))
+        })
+        .collect();
+    if !estaticas.is_empty() {
+        codigo.push_str("section .data\n");
+        for (nome, _) in &prog.globais {
+            if let Some(valores) = estaticas.get(nome) {
+                let valores: Vec<String> = valores.iter().map(|v| v.to_string()).collect();
+                codigo.push_str(&format!("{}: dq {}\n", nome, valores.join(", "))); // Um quadword por campo
+            }
+        }
+    }
+
+    // Declaração da seção BSS para alocar espaço para as demais variáveis globais
     codigo.push_str("section .bss\n");
-    for (nome, _) in &prog.globais {
-        codigo.push_str(&format!("{}: resq 1\n", nome)); // Reserva 8 bytes (1 quadword)
+    let nomes_globais = prog.globais.iter().map(|(nome, _)| nome).filter(|nome| !estaticas.contains_key(nome));
+    let nomes_globais = nomes_globais.chain(prog.vetores.iter().map(|(nome, _)| nome));
+    for nome in nomes_globais {
+        let tamanho = ctx.layout.tamanho(&ctx.layout.globais[nome]);
+        codigo.push_str(&format!("{}: resq {}\n", nome, tamanho / 8)); // Um quadword por elemento ou campo
     }
 
-    // Início da seção de código executável
+    // Início da seção de código executável. Programas que chamam funções externas são
+    // ligados com a libc, que se inicializa e depois chama main.
+    let usa_libc = !prog.externas.is_empty();
     codigo.push_str("section .text\n");
-    codigo.push_str("global _start\n"); // Ponto de entrada do programa
+    for externa in &prog.externas {
+        codigo.push_str(&format!("extern {}\n", externa.nome));
+    }
+    if biblioteca {
+        for func in prog.funcoes.iter().filter(|f| f.publica) {
+            codigo.push_str(&format!("global {}\n", func.nome));
+        }
+    } else if usa_libc {
+        codigo.push_str("global main\n");
+    } else {
+        codigo.push_str("global _start\n"); // Ponto de entrada do programa
+    }
 
-    // Gera o código das funções definidas no programa
+    // Gera o código das funções definidas no programa e, nas bibliotecas, os pontos
+    // de entrada das funções públicas
     for func in &prog.funcoes {
-        codigo.push_str(&gerar_funcao(func, &mut labels));
+        let label = label_funcao(&func.nome, &ctx);
+        codigo.push_str(&gerar_funcao(func, &label, &mut ctx));
+    }
+    if biblioteca {
+        for func in prog.funcoes.iter().filter(|f| f.publica) {
+            codigo.push_str(&gerar_exportada(func, &ctx));
+        }
+    }
+
+    // Gera o código do bloco principal (iniciado por _start, ou por main com a libc,
+    // que exige que rbx seja preservado); bibliotecas não têm bloco principal
+    if !biblioteca {
+        codigo.push_str(&gerar_principal(prog, &estaticas, usa_libc, &mut ctx));
+    }
+
+    // Rotina de erro usada pela verificação de limites
+    if opcoes.verificar_limites {
+        codigo.push_str("\n_erro_limites:\n");
+        codigo.push_str(&format!("mov rdi, {}\n", STATUS_FORA_DOS_LIMITES));
+        codigo.push_str("mov rax, 60\n");
+        codigo.push_str("syscall\n");
+    }
+
+    // Rotina de erro dos asserts: escreve a mensagem (endereço em rsi e tamanho em rdx)
+    // na saída de erro e encerra o programa
+    if ctx.usa_assert {
+        codigo.push_str("\n_erro_assercao:\n");
+        codigo.push_str("mov rdi, 2\n"); // stderr
+        codigo.push_str("mov rax, 1\n"); // syscall number de write
+        codigo.push_str("syscall\n");
+        codigo.push_str(&format!("mov rdi, {}\n", STATUS_ASSERCAO_FALHOU));
+        codigo.push_str("mov rax, 60\n");
+        codigo.push_str("syscall\n");
     }
 
-    // Gera o código do bloco principal (iniciado por _start)
-    codigo.push_str("\n_start:\n");
+    // Rotinas do runtime usadas pelo programa e as variáveis de que precisam
+    if ctx.rotinas.contains(&Rotina::Descarrega) && !ctx.externas.contains("fflush") {
+        codigo.push_str("extern fflush\n");
+    }
+    for rotina in &ctx.rotinas {
+        codigo.push_str(runtime::gerar_rotina(*rotina));
+    }
+    let dados: BTreeSet<&str> = ctx.rotinas.iter().filter_map(|r| runtime::dados(*r)).collect();
+    if !dados.is_empty() {
+        codigo.push_str("\nsection .bss\n");
+        for bloco in dados {
+            codigo.push_str(bloco);
+        }
+    }
 
-    // Inicializa as variáveis globais com as expressões correspondentes
-    for (nome, expr) in &prog.globais {
-        codigo.push_str(&gerar_expr(expr, &mut labels, &HashMap::new()));
-        codigo.push_str(&format!("mov [{}], rax\n", nome)); // Armazena em memória
+    // Literais de string terminados em zero, emitidos byte a byte, e tabelas de saltos.
+    // Em bibliotecas os endereços das tabelas são relocados na carga e ficam em .data.
+    if !ctx.strings.is_empty() || (!ctx.tabelas.is_empty() && !biblioteca) {
+        codigo.push_str("\nsection .rodata\n");
+    }
+    for (i, texto) in ctx.strings.iter().enumerate() {
+        let bytes: Vec<String> = texto.bytes().chain([0]).map(|b| b.to_string()).collect();
+        codigo.push_str(&format!("_str{}: db {}\n", i, bytes.join(",")));
+    }
+    if !ctx.tabelas.is_empty() && biblioteca {
+        codigo.push_str("\nsection .data\n");
+    }
+    for (i, destinos) in ctx.tabelas.iter().enumerate() {
+        codigo.push_str(&format!("_tabela{}: dq {}\n", i, destinos.join(",")));
+    }
+
+    // Ligado pelo cc, o objeto declara que não precisa de pilha executável
+    if usa_libc || biblioteca {
+        codigo.push_str("\nsection .note.GNU-stack noalloc noexec nowrite progbits\n");
+    }
+
+    codigo
+}
+
+// Gera o bloco principal: inicializa as globais que não estão em .data, executa os
+// comandos e termina o programa com o valor de retorno
+fn gerar_principal(prog: &Programa, estaticas: &HashMap<&String, Vec<i64>>, usa_libc: bool, ctx: &mut Contexto) -> String {
+    let mut codigo = String::new();
+    if usa_libc {
+        codigo.push_str("\nmain:\npush rbx\n");
+    } else {
+        codigo.push_str("\n_start:\n");
+    }
+
+    // Inicializa as demais variáveis globais, cada uma depois das globais que ela lê
+    let ordem = checker::ordem_inicializacao(prog).expect("Inicializações verificadas antes da geração");
+    for (nome, expr) in ordem.into_iter().map(|i| &prog.globais[i]) {
+        if !estaticas.contains_key(nome) {
+            codigo.push_str(&gerar_init(nome, expr, ctx));
+        }
     }
 
     // Gera os comandos principais do programa
     for cmd in &prog.principal {
-        codigo.push_str(&gerar_cmd(cmd, &mut labels, &HashMap::new()));
+        codigo.push_str(&gerar_cmd(cmd, ctx));
     }
 
     // Gera o valor de retorno do programa
-    codigo.push_str(&gerar_expr(&prog.retorno, &mut labels, &HashMap::new()));
-    codigo.push_str("mov rdi, rax\n"); // Passa retorno como argumento do exit
-    codigo.push_str("mov rax, 60\n");  // syscall number de exit
-    codigo.push_str("syscall\n");      // chama o kernel
+    codigo.push_str(&gerar_expr(&prog.retorno, ctx));
+    if let Some(l_retorno) = ctx.retorno.take() {
+        codigo.push_str(&format!("Lretorno{}:\n", l_retorno));
+    }
+    if usa_libc {
+        codigo.push_str("pop rbx\n");
+        codigo.push_str("ret\n"); // A libc encerra o programa com o valor de rax
+    } else {
+        codigo.push_str("mov rdi, rax\n"); // Passa retorno como argumento do exit
+        codigo.push_str("mov rax, 60\n");  // syscall number de exit
+        codigo.push_str("syscall\n");      // chama o kernel
+    }
+    codigo
+}
+
+// Label do código de uma função de nível superior. O nome das funções exportadas
+// fica com o ponto de entrada para C, e o corpo, que usa a convenção de Fun, recebe
+// um prefixo que nomes de Fun não podem ter.
+fn label_funcao(nome: &str, ctx: &Contexto) -> String {
+    match ctx.exportadas.contains(nome) {
+        true => format!("_fun_{}", nome),
+        false => nome.to_string(),
+    }
+}
+
+// Gera o ponto de entrada de uma função pública, chamado de C pelo System V AMD64
+// ABI: empilha os argumentos recebidos nos registradores na ordem de Fun e chama o
+// corpo da função. rbx é usado pelo código gerado e o ABI exige que seja preservado.
+fn gerar_exportada(func: &FunDecl, ctx: &Contexto) -> String {
+    let mut codigo = format!("\n{}:\n", func.nome);
+    codigo.push_str("push rbp\n");
+    codigo.push_str("mov rbp, rsp\n");
+    codigo.push_str("push rbx\n");
+    let n = func.parametros.len();
+    for registrador in REGISTRADORES_ARGUMENTOS[..n].iter().rev() {
+        codigo.push_str(&format!("push {}\n", registrador));
+    }
+    codigo.push_str(&format!("call {}\n", label_funcao(&func.nome, ctx)));
+    if n > 0 {
+        codigo.push_str(&format!("add rsp, {}\n", n * 8));
+    }
+    codigo.push_str("pop rbx\n");
+    codigo.push_str("pop rbp\n");
+    codigo.push_str("ret\n");
+    codigo
+}
+
+// Gera o cabeçalho C com os protótipos das funções públicas de uma biblioteca
+pub fn gerar_cabecalho(prog: &Programa, nome: &str) -> String {
+    let guarda = format!("{}_H", nome.to_uppercase());
+    let tipo_c = |tipo: Tipo| match tipo {
+        Tipo::I64 => "int64_t",
+        Tipo::U64 => "uint64_t",
+    };
+    let mut codigo = format!("#ifndef {0}\n#define {0}\n\n#include <stdint.h>\n\n", guarda);
+    for func in prog.funcoes.iter().filter(|f| f.publica) {
+        let parametros: Vec<String> = func
+            .parametros
+            .iter()
+            .map(|p| format!("{} {}", tipo_c(func.tipos.get(p).copied().unwrap_or_default()), p))
+            .collect();
+        let parametros = if parametros.is_empty() { "void".to_string() } else { parametros.join(", ") };
+        let retorno = match valores_retorno(func) {
+            0 => "void",
+            _ => tipo_c(func.tipo_retorno),
+        };
+        codigo.push_str(&format!("{} {}({});\n", retorno, func.nome, parametros));
+    }
+    codigo.push_str(&format!("\n#endif /* {} */\n", guarda));
+    codigo
+}
+
+// Gera a chamada de uma rotina do runtime, registrando que ela deve ser emitida
+fn chamar_rotina(rotina: Rotina, ctx: &mut Contexto) -> String {
+    ctx.rotinas.insert(rotina);
+    format!("call {}\n", runtime::label(rotina))
+}
+
+// Chama uma rotina de escrita; com a libc, a saída que ela guarda no buffer sai antes
+fn chamar_escrita(rotina: Rotina, ctx: &mut Contexto) -> String {
+    let mut codigo = String::new();
+    if ctx.usa_libc {
+        codigo.push_str(&chamar_rotina(Rotina::Descarrega, ctx));
+    }
+    codigo.push_str(&chamar_rotina(rotina, ctx));
+    codigo
+}
+
+// Índice do literal em .rodata; textos iguais compartilham o mesmo literal
+fn indice_string(texto: &str, ctx: &mut Contexto) -> usize {
+    match ctx.strings.iter().position(|s| s == texto) {
+        Some(i) => i,
+        None => {
+            ctx.strings.push(texto.to_string());
+            ctx.strings.len() - 1
+        }
+    }
+}
+
+// Onde um nome está declarado, visto da função atual
+enum Ref {
+    Local { saltos: usize, local: Local },      // Variável de uma função: 0 é a atual, 1 a que a declarou, ...
+    Global,                                     // Variável global
+    Aninhada { saltos: usize, label: String },  // Função aninhada e a distância até o quadro que a declarou
+    Funcao,                                     // Função de nível superior
+    Constante(i64),                             // Constante e seu valor
+    Externa,                                    // Função externa (C)
+    Desconhecido,
+}
+
+// Resolve um nome do nível mais interno para o mais externo
+fn resolver(nome: &str, ctx: &Contexto) -> Ref {
+    for saltos in 0..=ctx.envolventes.len() {
+        let quadro = match saltos {
+            0 => &ctx.quadro,
+            _ => &ctx.envolventes[ctx.envolventes.len() - saltos],
+        };
+        if let Some(local) = quadro.locais.get(nome) {
+            return Ref::Local { saltos, local: local.clone() };
+        }
+        let nivel = ctx.aninhadas.len().checked_sub(saltos + 1).map(|i| &ctx.aninhadas[i]);
+        if let Some(label) = nivel.and_then(|aninhadas| aninhadas.get(nome)) {
+            return Ref::Aninhada { saltos, label: label.clone() };
+        }
+    }
+    if let Some(valor) = ctx.constantes.get(nome) {
+        Ref::Constante(*valor)
+    } else if ctx.layout.globais.contains_key(nome) {
+        Ref::Global
+    } else if ctx.funcoes.contains(nome) {
+        Ref::Funcao
+    } else if ctx.externas.contains(nome) {
+        Ref::Externa
+    } else {
+        Ref::Desconhecido
+    }
+}
+
+// Gera código que segue `saltos` links estáticos a partir de rbp, deixando o
+// quadro encontrado no registrador
+fn seguir_links(saltos: usize, registrador: &str) -> String {
+    let mut codigo = format!("mov {}, [rbp+16]\n", registrador);
+    for _ in 1..saltos {
+        codigo.push_str(&format!("mov {0}, [{0}+16]\n", registrador));
+    }
+    codigo
+}
+
+// Retorna o código de preparação e o operando de memória de uma variável
+// deslocado de `extra` bytes: local (pilha), de uma função envolvente (através
+// do link estático, em rcx) ou global
+fn operando_em(nome: &str, extra: isize, ctx: &Contexto) -> (String, String) {
+    match resolver(nome, ctx) {
+        Ref::Local { saltos, local } => {
+            let offset = local.offset + extra;
+            let sinal = if offset < 0 { "-" } else { "+" };
+            if saltos == 0 {
+                (String::new(), format!("[rbp{}{}]", sinal, offset.abs()))
+            } else {
+                (seguir_links(saltos, "rcx"), format!("[rcx{}{}]", sinal, offset.abs()))
+            }
+        }
+        _ if extra != 0 => (String::new(), format!("[{}+{}]", nome, extra)),
+        _ => (String::new(), format!("[{}]", nome)),
+    }
+}
+
+// Retorna o código de preparação e o operando de memória de uma variável
+fn operando(nome: &str, ctx: &Contexto) -> (String, String) {
+    operando_em(nome, 0, ctx)
+}
+
+// Retorna a forma da variável visível com esse nome (locais escondem globais)
+fn forma(nome: &str, ctx: &Contexto) -> Forma {
+    match resolver(nome, ctx) {
+        Ref::Local { local, .. } => local.forma,
+        _ => ctx.layout.globais.get(nome).cloned().unwrap_or(Forma::Escalar),
+    }
+}
+
+// Retorna o tamanho do vetor visível com esse nome
+fn tamanho_vetor(nome: &str, ctx: &Contexto) -> Option<usize> {
+    match forma(nome, ctx) {
+        Forma::Vetor(n) => Some(n),
+        _ => None,
+    }
+}
+
+// Retorna o código de preparação e o operando de memória do campo `nome.campo`
+fn operando_campo(nome: &str, campo: &str, ctx: &Contexto) -> (String, String) {
+    let tipo = match forma(nome, ctx) {
+        Forma::Registro(tipo) => tipo,
+        _ => panic!("'{}' não é um registro", nome),
+    };
+    let offset = ctx
+        .layout
+        .offset_campo(&tipo, campo)
+        .unwrap_or_else(|| panic!("Registro '{}' não tem o campo '{}'", tipo, campo));
+    operando_em(nome, offset, ctx)
+}
+
+// Monta uma instrução sobre um operando de memória, precedida da sua preparação
+fn instrucao(modelo: &str, (prep, operando): (String, String)) -> String {
+    format!("{}{}\n", prep, modelo.replace("{}", &operando))
+}
+
+// Gera código que deixa em rax o endereço de uma variável, elemento, campo ou desreferência
+fn gerar_endereco(expr: &Expr, ctx: &mut Contexto) -> String {
+    match expr {
+        // O endereço de uma função pública é o do corpo, chamado com os argumentos na pilha,
+        // e não o do ponto de entrada para C
+        Expr::Var(nome) if matches!(resolver(nome, ctx), Ref::Funcao) => {
+            format!("lea rax, [{}]\n", label_funcao(nome, ctx))
+        }
+        Expr::Var(nome) => instrucao("lea rax, {}", operando(nome, ctx)),
+        Expr::Indice { nome, indice } => gerar_elemento(nome, indice, ctx),
+        Expr::Campo { nome, campo } => instrucao("lea rax, {}", operando_campo(nome, campo, ctx)),
+        Expr::Deref(ptr) => gerar_expr(ptr, ctx),
+        _ => panic!("Expressão sem endereço: {:?}", expr),
+    }
+}
+
+// Gera a inicialização de uma variável declarada: registros recebem cada
+// campo (os não informados valem 0), as demais variáveis o valor da expressão
+fn gerar_init(nome: &str, expr: &Expr, ctx: &mut Contexto) -> String {
+    let mut codigo = String::new();
+    if let Expr::Desestruturacao { nomes, chamada } = expr {
+        codigo.push_str(&gerar_desestruturacao(nomes, chamada, ctx));
+    } else if let Expr::Componente(_) = expr {
+        // Já preenchida pela desestruturação que a declarou
+    } else if let Expr::Registro { tipo, campos } = expr {
+        for campo in ctx.layout.registros[tipo].clone() {
+            match campos.iter().find(|(c, _)| *c == campo) {
+                Some((_, valor)) => codigo.push_str(&gerar_expr(valor, ctx)),
+                None => codigo.push_str("xor rax, rax\n"),
+            }
+            codigo.push_str(&instrucao("mov {}, rax", operando_campo(nome, &campo, ctx)));
+        }
+    } else {
+        codigo.push_str(&gerar_expr(expr, ctx));
+        codigo.push_str(&instrucao("mov {}, rax", operando(nome, ctx))); // Armazena em memória
+    }
+    codigo
+}
+
+// Gera a chamada de uma função que devolve uma tupla e guarda cada valor em uma
+// variável: dois valores chegam em rax e rdx, mais que isso no buffer da pilha
+fn gerar_desestruturacao(nomes: &[String], chamada: &Expr, ctx: &mut Contexto) -> String {
+    let (nome, args, nomeados) = match chamada {
+        Expr::Call { nome, args, nomeados, .. } => (nome, args, nomeados),
+        _ => panic!("Desestruturação sem chamada: {:?}", chamada),
+    };
+    if nomes.len() <= MAX_VALORES_REGISTRADORES {
+        let mut codigo = gerar_chamada(nome, args, nomeados, 0, ctx);
+        codigo.push_str(&instrucao("mov {}, rax", operando(&nomes[0], ctx)));
+        codigo.push_str(&instrucao("mov {}, rdx", operando(&nomes[1], ctx)));
+        return codigo;
+    }
+    let mut codigo = gerar_chamada(nome, args, nomeados, nomes.len(), ctx);
+    for nome in nomes {
+        codigo.push_str("pop rax\n"); // Retira o buffer da pilha, um valor por vez
+        codigo.push_str(&instrucao("mov {}, rax", operando(nome, ctx)));
+    }
+    codigo
+}
 
+// Gera o valor de retorno de uma função: tuplas vão em rax e rdx ou, quando maiores,
+// para o buffer cujo endereço foi recebido
+fn gerar_retorno(retorno: &Expr, ctx: &mut Contexto) -> String {
+    let valores = match retorno {
+        Expr::Tupla(valores) => valores,
+        _ => return gerar_expr(retorno, ctx),
+    };
+    let mut codigo = String::new();
+    match ctx.quadro.buffer_retorno {
+        None => {
+            codigo.push_str(&gerar_expr(&valores[1], ctx));
+            codigo.push_str("push rax\n");
+            codigo.push_str(&gerar_expr(&valores[0], ctx));
+            codigo.push_str("pop rdx\n");
+        }
+        Some(offset) => {
+            for (i, valor) in valores.iter().enumerate() {
+                codigo.push_str(&gerar_expr(valor, ctx));
+                codigo.push_str(&format!("mov rbx, [rbp+{}]\n", offset));
+                codigo.push_str(&format!("mov [rbx+{}], rax\n", i * 8));
+            }
+        }
+    }
     codigo
 }
 
-// Gera o código de uma função
-fn gerar_funcao(func: &FunDecl, labels: &mut usize) -> String {
+// Gera os comandos do corpo de um laço, com `l_fim` como destino dos breaks
+fn gerar_corpo_laco(body: &[Cmd], l_fim: usize, ctx: &mut Contexto) -> String {
+    ctx.lacos.push(l_fim);
+    let codigo = body.iter().map(|c| gerar_cmd(c, ctx)).collect();
+    ctx.lacos.pop();
+    codigo
+}
+
+// Indica se a expressão pode ser avaliada mesmo quando o programa não a pediria: sem
+// chamadas, leituras, alocações e operações que podem falhar (divisões, acessos a
+// elementos e desreferências)
+fn sem_efeitos(expr: &Expr) -> bool {
+    match expr {
+        Expr::Const(_) | Expr::Str(_) | Expr::Var(_) | Expr::Campo { .. } => true,
+        Expr::Endereco(alvo) => matches!(alvo.as_ref(), Expr::Var(_) | Expr::Campo { .. }),
+        Expr::OpBin { operador, esq, dir } => {
+            !matches!(operador.as_str(), "/" | "%" | "u/" | "u%") && sem_efeitos(esq) && sem_efeitos(dir)
+        }
+        Expr::Condicional { cond, entao, senao } => sem_efeitos(cond) && sem_efeitos(entao) && sem_efeitos(senao),
+        _ => false,
+    }
+}
+
+// Gera um if usado como expressão, deixando o valor escolhido em rax
+fn gerar_condicional(cond: &Expr, entao: &Expr, senao: &Expr, ctx: &mut Contexto) -> String {
     let mut codigo = String::new();
-    let mut deslocamentos = HashMap::new();
-    let mut offset = 16; // Parâmetros começam depois do rbp
+    if sem_efeitos(entao) && sem_efeitos(senao) {
+        // Os dois valores são calculados e a condição escolhe um sem desvios
+        codigo.push_str(&gerar_expr(senao, ctx));
+        codigo.push_str("push rax\n");
+        codigo.push_str(&gerar_expr(entao, ctx));
+        codigo.push_str("push rax\n");
+        codigo.push_str(&gerar_expr(cond, ctx));
+        codigo.push_str("cmp rax, 0\n");
+        codigo.push_str("pop rax\n"); // pop não altera as flags
+        codigo.push_str("pop rbx\n");
+        codigo.push_str("cmove rax, rbx\n");
+        return codigo;
+    }
+
+    let l_falso = ctx.labels;
+    ctx.labels += 1;
+    let l_fim = ctx.labels;
+    ctx.labels += 1;
+    codigo.push_str(&gerar_expr(cond, ctx));
+    codigo.push_str("cmp rax, 0\n");
+    codigo.push_str(&format!("je Lfalso{}\n", l_falso));
+    codigo.push_str(&gerar_expr(entao, ctx));
+    codigo.push_str(&format!("jmp Lfim{}\n", l_fim));
+    codigo.push_str(&format!("Lfalso{}:\n", l_falso));
+    codigo.push_str(&gerar_expr(senao, ctx));
+    codigo.push_str(&format!("Lfim{}:\n", l_fim));
+    codigo
+}
 
-    // Atribui offset para parâmetros (a partir de rbp+16, rbp+24, etc.)
-    for param in &func.parametros {
-        deslocamentos.insert(param.clone(), offset);
-        offset += 8;
+// Valor conhecido em tempo de compilação de um literal ou constante
+fn valor_imediato(expr: &Expr, ctx: &Contexto) -> Option<i64> {
+    match expr {
+        Expr::Const(v) => Some(*v),
+        Expr::Var(nome) => match resolver(nome, ctx) {
+            Ref::Constante(v) => Some(v),
+            _ => None,
+        },
+        _ => None,
     }
+}
 
-    // Atribui offset negativo para variáveis locais (rbp-8, rbp-16, etc.)
-    let mut var_offset = -8;
-    for (nome, _) in &func.variaveis {
-        deslocamentos.insert(nome.clone(), var_offset);
-        var_offset -= 8;
+// Gera uma atribuição composta, que atualiza o valor direto na memória: somas e
+// subtrações usam o operando de memória com o valor em rbx ou imediato; multiplicação,
+// divisão e resto passam por rax. Alvos com endereço calculado são acessados por rcx.
+fn gerar_atrib_composta(alvo: &Expr, operador: &str, expr: &Expr, ctx: &mut Contexto) -> String {
+    let imediato = valor_imediato(expr, ctx).filter(|v| cabe_em_imm32(*v));
+    let mut codigo = String::new();
+    if imediato.is_none() {
+        codigo.push_str(&gerar_expr(expr, ctx));
+        codigo.push_str("push rax\n"); // Salva o valor enquanto calcula o endereço
+    }
+    let (prep, memoria) = match alvo {
+        Expr::Var(nome) => operando(nome, ctx),
+        Expr::Campo { nome, campo } => operando_campo(nome, campo, ctx),
+        _ => (gerar_endereco(alvo, ctx) + "mov rcx, rax\n", "[rcx]".to_string()),
+    };
+    codigo.push_str(&prep);
+    let valor = match imediato {
+        Some(v) => v.to_string(),
+        None => {
+            codigo.push_str("pop rbx\n");
+            "rbx".to_string()
+        }
+    };
+
+    match operador {
+        "+" | "-" => {
+            let instrucao = if operador == "+" { "add" } else { "sub" };
+            let tamanho = if imediato.is_some() { "qword " } else { "" };
+            codigo.push_str(&format!("{} {}{}, {}\n", instrucao, tamanho, memoria, valor));
+        }
+        "*" => {
+            match imediato {
+                Some(v) => codigo.push_str(&format!("imul rax, qword {}, {}\n", memoria, v)),
+                None => codigo.push_str(&format!("mov rax, {}\nimul rax, rbx\n", memoria)),
+            }
+            codigo.push_str(&format!("mov {}, rax\n", memoria));
+        }
+        // Divisão e resto, com ou sem sinal: o divisor vai para rbx
+        _ => {
+            if let Some(v) = imediato {
+                codigo.push_str(&format!("mov rbx, {}\n", v));
+            }
+            codigo.push_str(&format!("mov rax, {}\n", memoria));
+            if operador.starts_with('u') {
+                codigo.push_str("xor rdx, rdx\ndiv rbx\n");
+            } else {
+                codigo.push_str("cqo\nidiv rbx\n");
+            }
+            let resultado = if operador.ends_with('%') { "rdx" } else { "rax" };
+            codigo.push_str(&format!("mov {}, {}\n", memoria, resultado));
+        }
+    }
+    codigo
+}
+
+// Indica se o valor cabe no imediato de 32 bits com sinal aceito pela maioria das
+// instruções, que o estendem para 64 bits
+fn cabe_em_imm32(valor: i64) -> bool {
+    i32::try_from(valor).is_ok()
+}
+
+// Carrega um valor imediato em rax; valores fora dos 32 bits com sinal exigem a
+// forma da instrução com imediato de 64 bits
+fn carregar_imediato(valor: i64) -> String {
+    if cabe_em_imm32(valor) {
+        format!("mov rax, {}\n", valor)
+    } else {
+        format!("mov rax, strict qword {}\n", valor)
+    }
+}
+
+// Gera um comando match. Quando os valores são muitos e próximos, o valor menos o
+// menor deles indexa uma tabela de saltos; senão, é comparado com cada valor.
+fn gerar_match(valor: &Expr, bracos: &[(Vec<Expr>, Vec<Cmd>)], padrao: &[Cmd], ctx: &mut Contexto) -> String {
+    let rotulo = ctx.labels;
+    ctx.labels += 1;
+    let caso = |i: usize| format!("Lcaso{}_{}", rotulo, i);
+    let l_padrao = format!("Lpadrao{}", rotulo);
+
+    // Valor de cada padrão e o braço ao qual ele leva
+    let mut casos: Vec<(i64, usize)> = bracos
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (padroes, _))| padroes.iter().map(move |p| (p, i)))
+        .map(|(p, i)| (consteval::avaliar(p, &ctx.constantes).expect("Padrões verificados antes da geração"), i))
+        .collect();
+    casos.sort();
+
+    let mut codigo = gerar_expr(valor, ctx);
+    let (menor, maior) = match (casos.first(), casos.last()) {
+        (Some(&(menor, _)), Some(&(maior, _))) => (menor, maior),
+        _ => (0, 0),
+    };
+    let entradas = maior as i128 - menor as i128 + 1;
+    if casos.len() >= MIN_CASOS_TABELA && entradas <= MAX_ENTRADAS_TABELA && entradas <= 2 * casos.len() as i128 {
+        // Comparação sem sinal: valores abaixo do menor também viram índices grandes
+        if cabe_em_imm32(menor) {
+            codigo.push_str(&format!("sub rax, {}\n", menor));
+        } else {
+            codigo.push_str(&format!("mov rcx, strict qword {}\nsub rax, rcx\n", menor));
+        }
+        codigo.push_str(&format!("cmp rax, {}\n", entradas - 1));
+        codigo.push_str(&format!("ja {}\n", l_padrao));
+        codigo.push_str(&format!("lea rcx, [_tabela{}]\n", ctx.tabelas.len()));
+        codigo.push_str("jmp qword [rcx+rax*8]\n");
+        let mut destinos = vec![l_padrao.clone(); entradas as usize];
+        for &(v, i) in &casos {
+            destinos[(v as i128 - menor as i128) as usize] = caso(i);
+        }
+        ctx.tabelas.push(destinos);
+    } else {
+        for &(v, i) in &casos {
+            if cabe_em_imm32(v) {
+                codigo.push_str(&format!("cmp rax, {}\n", v));
+            } else {
+                codigo.push_str(&format!("mov rcx, strict qword {}\ncmp rax, rcx\n", v));
+            }
+            codigo.push_str(&format!("je {}\n", caso(i)));
+        }
+        codigo.push_str(&format!("jmp {}\n", l_padrao));
+    }
+
+    // Braços, cada um terminando com um salto para o fim; o braço `_` vem por último
+    let l_fim = format!("Lfim{}", rotulo);
+    for (i, (_, cmds)) in bracos.iter().enumerate() {
+        codigo.push_str(&format!("{}:\n", caso(i)));
+        for cmd in cmds {
+            codigo.push_str(&gerar_cmd(cmd, ctx));
+        }
+        codigo.push_str(&format!("jmp {}\n", l_fim));
+    }
+    codigo.push_str(&format!("{}:\n", l_padrao));
+    for cmd in padrao {
+        codigo.push_str(&gerar_cmd(cmd, ctx));
+    }
+    codigo.push_str(&format!("{}:\n", l_fim));
+    codigo
+}
+
+// Gera código que deixa em rax o endereço do elemento `nome[indice]`.
+// Em vetores o primeiro elemento está na própria variável; em qualquer outra
+// variável o valor guardado é tratado como ponteiro para o primeiro elemento.
+fn gerar_elemento(nome: &str, indice: &Expr, ctx: &mut Contexto) -> String {
+    let mut codigo = gerar_expr(indice, ctx);
+    match tamanho_vetor(nome, ctx) {
+        Some(tamanho) => {
+            if ctx.verificar_limites {
+                // Comparação sem sinal: índices negativos também são rejeitados
+                codigo.push_str(&format!("cmp rax, {}\n", tamanho));
+                codigo.push_str("jae _erro_limites\n");
+            }
+            codigo.push_str(&instrucao("lea rbx, {}", operando(nome, ctx))); // Endereço do primeiro elemento
+        }
+        None => codigo.push_str(&instrucao("mov rbx, {}", operando(nome, ctx))), // Ponteiro, sem tamanho conhecido
+    }
+    codigo.push_str("lea rax, [rbx+rax*8]\n");
+    codigo
+}
+
+// Gera o código de uma função (com o label informado) e de suas funções aninhadas
+fn gerar_funcao(func: &FunDecl, label: &str, ctx: &mut Contexto) -> String {
+    let mut codigo = String::new();
+
+    // Parâmetros em rbp+16, rbp+24, ...; variáveis locais abaixo de rbp.
+    // Dentro de outra função, o quadro dela passa a ser acessado pelo link estático.
+    let aninhada = !ctx.aninhadas.is_empty();
+    let quadro = ctx.layout.quadro(func, aninhada);
+    let anterior = std::mem::replace(&mut ctx.quadro, quadro);
+    if aninhada {
+        ctx.envolventes.push(anterior);
+    }
+    let labels = func.aninhadas.iter().map(|f| (f.nome.clone(), format!("{}.{}", label, f.nome)));
+    ctx.aninhadas.push(labels.collect());
+    for filha in &func.aninhadas {
+        let assinatura = (filha.parametros.clone(), filha.padroes.clone());
+        ctx.parametros.insert(format!("{}.{}", label, filha.nome), assinatura);
     }
 
     // Cabeçalho da função
-    codigo.push_str(&format!("\n{}:\n", func.nome));
+    codigo.push_str(&format!("\n{}:\n", label));
     codigo.push_str("push rbp\n");
     codigo.push_str("mov rbp, rsp\n");
 
     // Aloca espaço para variáveis locais
-    let tamanho_stack = func.variaveis.len() * 8;
+    let tamanho_stack = ctx.quadro.tamanho;
     if tamanho_stack > 0 {
         codigo.push_str(&format!("sub rsp, {}\n", tamanho_stack));
     }
 
     // Inicializa variáveis locais
     for (nome, expr) in &func.variaveis {
-        codigo.push_str(&gerar_expr(expr, labels, &deslocamentos));
-        if let Some(offset) = deslocamentos.get(nome) {
-            let sinal = if *offset < 0 { "-" } else { "+" };
-            codigo.push_str(&format!("mov [rbp{}{}], rax\n", sinal, offset.abs()));
-        }
+        codigo.push_str(&gerar_init(nome, expr, ctx));
     }
 
     // Gera os comandos da função
     for cmd in &func.comandos {
-        codigo.push_str(&gerar_cmd(cmd, labels, &deslocamentos));
+        codigo.push_str(&gerar_cmd(cmd, ctx));
     }
 
-    // Gera o valor de retorno da função
-    codigo.push_str(&gerar_expr(&func.retorno, labels, &deslocamentos));
+    // Gera o valor de retorno da função; os returns antecipados saltam para depois dele.
+    // Sem o return final, rax vale 0: procedimentos chegam ao fim do corpo, e as funções que
+    // devolvem valor só não têm o return final quando o verificador garante que o fim não é alcançado.
+    match &func.retorno {
+        Some(retorno) => codigo.push_str(&gerar_retorno(retorno, ctx)),
+        None => codigo.push_str("xor rax, rax\n"),
+    }
+    if let Some(l_retorno) = ctx.retorno.take() {
+        codigo.push_str(&format!("Lretorno{}:\n", l_retorno));
+    }
 
     // Libera espaço de pilha
     if tamanho_stack > 0 {
@@ -102,31 +816,135 @@ fn gerar_funcao(func: &FunDecl, labels: &mut usize) -> String {
     codigo.push_str("pop rbp\n");
     codigo.push_str("ret\n");
 
+    // As funções aninhadas são emitidas depois, enxergando este quadro
+    for filha in &func.aninhadas {
+        let label_filha = format!("{}.{}", label, filha.nome);
+        codigo.push_str(&gerar_funcao(filha, &label_filha, ctx));
+    }
+
+    // Restaura o quadro envolvente; o bloco principal não enxerga variáveis de funções
+    ctx.aninhadas.pop();
+    ctx.quadro = if aninhada { ctx.envolventes.pop().unwrap() } else { Quadro::default() };
+
+    codigo
+}
+
+// Argumentos de uma chamada na ordem dos parâmetros: depois dos posicionais vêm os
+// nomeados e, para os parâmetros opcionais não informados, os valores padrão, já
+// calculados (uma local de quem chama não pode esconder a constante usada no padrão).
+// Chamadas indiretas e externas só têm argumentos posicionais.
+fn argumentos(nome: &str, args: &[Expr], nomeados: &[(String, Expr)], ctx: &Contexto) -> Vec<Expr> {
+    let label = match resolver(nome, ctx) {
+        Ref::Funcao => nome.to_string(),
+        Ref::Aninhada { label, .. } => label,
+        _ => return args.to_vec(),
+    };
+    let (parametros, padroes) = &ctx.parametros[&label];
+    parametros
+        .iter()
+        .enumerate()
+        .map(|(i, param)| match args.get(i) {
+            Some(arg) => arg.clone(),
+            None => match nomeados.iter().find(|(nome, _)| nome == param) {
+                Some((_, arg)) => arg.clone(),
+                None => {
+                    let padrao = padroes.get(param).expect("Argumentos verificados antes da geração");
+                    Expr::Const(consteval::avaliar(padrao, &ctx.constantes).expect("Padrões verificados antes da geração"))
+                }
+            },
+        })
+        .collect()
+}
+
+// Gera uma chamada de função. Funções que devolvem tuplas maiores que os registradores
+// de retorno recebem o endereço de um buffer de `buffer` quadwords, reservado na pilha
+// antes dos argumentos e deixado no topo da pilha depois da chamada.
+fn gerar_chamada(nome: &str, args: &[Expr], nomeados: &[(String, Expr)], buffer: usize, ctx: &mut Contexto) -> String {
+    let args = argumentos(nome, args, nomeados, ctx);
+    let mut codigo = String::new();
+    if buffer > 0 {
+        codigo.push_str(&format!("sub rsp, {}\n", buffer * 8)); // Reserva o buffer de retorno
+    }
+    for arg in args.iter().rev() {
+        codigo.push_str(&gerar_expr(arg, ctx));
+        codigo.push_str("push rax\n"); // Empilha argumentos
+    }
+    let mut empilhados = args.len();
+    if buffer > 0 {
+        codigo.push_str(&format!("lea rax, [rsp+{}]\n", args.len() * 8));
+        codigo.push_str("push rax\n"); // Endereço do buffer, depois dos argumentos
+        empilhados += 1;
+    }
+    match resolver(nome, ctx) {
+        // Chamada indireta: a variável guarda o endereço da função
+        Ref::Local { .. } | Ref::Global => {
+            codigo.push_str(&instrucao("mov rax, {}", operando(nome, ctx)));
+            codigo.push_str("call rax\n");
+        }
+        // Função aninhada: empilha o quadro de quem a declarou como link estático
+        Ref::Aninhada { saltos, label } => {
+            if saltos == 0 {
+                codigo.push_str("push rbp\n");
+            } else {
+                codigo.push_str(&seguir_links(saltos, "rax"));
+                codigo.push_str("push rax\n");
+            }
+            codigo.push_str(&format!("call {}\n", label));
+            empilhados += 1;
+        }
+        // Função externa: argumentos nos registradores e pilha alinhada em 16 bytes.
+        // r12 é preservado pela função chamada e guarda o rsp original. O resultado
+        // fica em rax sem extensão de sinal: externas devem devolver valores de 64 bits.
+        Ref::Externa => {
+            for registrador in &REGISTRADORES_ARGUMENTOS[..args.len()] {
+                codigo.push_str(&format!("pop {}\n", registrador));
+            }
+            codigo.push_str("push r12\n");
+            codigo.push_str("mov r12, rsp\n");
+            codigo.push_str("and rsp, -16\n");
+            codigo.push_str("xor eax, eax\n"); // Nenhum argumento em registradores vetoriais
+            if ctx.biblioteca {
+                codigo.push_str(&format!("call {} wrt ..plt\n", nome)); // A libc pode estar em biblioteca dinâmica
+            } else {
+                codigo.push_str(&format!("call {}\n", nome));
+            }
+            codigo.push_str("mov rsp, r12\n");
+            codigo.push_str("pop r12\n");
+            empilhados = 0;
+        }
+        _ => codigo.push_str(&format!("call {}\n", label_funcao(nome, ctx))), // Chama a função
+    }
+    if empilhados > 0 {
+        codigo.push_str(&format!("add rsp, {}\n", empilhados * 8)); // Desempilha
+    }
     codigo
 }
 
 // Gera código para uma expressão
-fn gerar_expr(expr: &Expr, labels: &mut usize, deslocamentos: &HashMap<String, isize>) -> String {
+fn gerar_expr(expr: &Expr, ctx: &mut Contexto) -> String {
     match expr {
         // Expressão constante: move o valor para rax
-        Expr::Const(v) => format!("mov rax, {}\n", v),
+        Expr::Const(v) => carregar_imediato(*v),
+
+        // Literal de string: rax recebe o endereço do texto em .rodata
+        Expr::Str(texto) => format!("lea rax, [_str{}]\n", indice_string(texto, ctx)),
 
         // Variável: local (pilha) ou global (memória)
-        Expr::Var(nome) => {
-            if let Some(offset) = deslocamentos.get(nome) {
-                let sinal = if *offset < 0 { "-" } else { "+" };
-                format!("mov rax, [rbp{}{}]\n", sinal, offset.abs())
-            } else {
-                format!("mov rax, [{}]\n", nome)
-            }
-        }
+        // Vetores são usados pelo endereço do primeiro elemento, funções pelo
+        // endereço de seu código e constantes pelo valor imediato
+        Expr::Var(nome) => match (resolver(nome, ctx), forma(nome, ctx)) {
+            (Ref::Constante(valor), _) => carregar_imediato(valor),
+            (Ref::Funcao, _) => format!("lea rax, [{}]\n", label_funcao(nome, ctx)),
+            (_, Forma::Vetor(_)) => instrucao("lea rax, {}", operando(nome, ctx)),
+            _ => instrucao("mov rax, {}", operando(nome, ctx)),
+        },
 
         // Operação binária
         Expr::OpBin { operador, esq, dir } => {
             let mut codigo = String::new();
-            codigo.push_str(&gerar_expr(dir, labels, deslocamentos)); // Avalia direita primeiro
+            codigo.push_str(&gerar_expr(dir, ctx)); // Avalia direita primeiro
             codigo.push_str("push rax\n"); // Salva resultado
-            codigo.push_str(&gerar_expr(esq, labels, deslocamentos)); // Avalia esquerda
+            codigo.push_str(&gerar_expr(esq, ctx)); // Avalia esquerda
             codigo.push_str("pop rbx\n"); // Recupera direita em rbx
 
             match operador.as_str() {
@@ -137,6 +955,23 @@ fn gerar_expr(expr: &Expr, labels: &mut usize, deslocamentos: &HashMap<String, i
                     codigo.push_str("cqo\n"); // Estende rax para rdx:rax
                     codigo.push_str("idiv rbx\n"); // Divide rdx:rax por rbx, resultado em rax
                 }
+                "u/" => {
+                    codigo.push_str("xor rdx, rdx\n"); // Sem sinal: a parte alta do dividendo é zero
+                    codigo.push_str("div rbx\n");
+                }
+                "%" => {
+                    codigo.push_str("cqo\n");
+                    codigo.push_str("idiv rbx\n");
+                    codigo.push_str("mov rax, rdx\n"); // O resto da divisão fica em rdx
+                }
+                "u%" => {
+                    codigo.push_str("xor rdx, rdx\n");
+                    codigo.push_str("div rbx\n");
+                    codigo.push_str("mov rax, rdx\n");
+                }
+                "<<" => codigo.push_str("mov rcx, rbx\nshl rax, cl\n"),
+                ">>" => codigo.push_str("mov rcx, rbx\nsar rax, cl\n"), // Aritmético: preserva o sinal
+                "u>>" => codigo.push_str("mov rcx, rbx\nshr rax, cl\n"), // Lógico: entra zero à esquerda
                 "==" => {
                     codigo.push_str("xor rcx, rcx\n");
                     codigo.push_str("cmp rax, rbx\n");
@@ -155,6 +990,18 @@ fn gerar_expr(expr: &Expr, labels: &mut usize, deslocamentos: &HashMap<String, i
                     codigo.push_str("setg cl\n");
                     codigo.push_str("mov rax, rcx\n");
                 }
+                "u<" => {
+                    codigo.push_str("xor rcx, rcx\n");
+                    codigo.push_str("cmp rax, rbx\n");
+                    codigo.push_str("setb cl\n");
+                    codigo.push_str("mov rax, rcx\n");
+                }
+                "u>" => {
+                    codigo.push_str("xor rcx, rcx\n");
+                    codigo.push_str("cmp rax, rbx\n");
+                    codigo.push_str("seta cl\n");
+                    codigo.push_str("mov rax, rcx\n");
+                }
                 _ => panic!("Operador inválido: {}", operador),
             }
 
@@ -162,83 +1009,269 @@ fn gerar_expr(expr: &Expr, labels: &mut usize, deslocamentos: &HashMap<String, i
         }
 
         // Chamada de função
-        Expr::Call { nome, args } => {
-            let mut codigo = String::new();
-            for arg in args.iter().rev() {
-                codigo.push_str(&gerar_expr(arg, labels, deslocamentos));
-                codigo.push_str("push rax\n"); // Empilha argumentos
-            }
-            codigo.push_str(&format!("call {}\n", nome)); // Chama a função
-            if !args.is_empty() {
-                codigo.push_str(&format!("add rsp, {}\n", args.len() * 8)); // Desempilha
-            }
+        Expr::Call { nome, args, nomeados, .. } => gerar_chamada(nome, args, nomeados, 0, ctx),
+
+        // If como expressão: cmov quando os dois valores podem ser calculados sem efeitos,
+        // desvios quando só um deles pode ser avaliado
+        Expr::Condicional { cond, entao, senao } => gerar_condicional(cond, entao, senao, ctx),
+
+        // Tuplas só aparecem no retorno e desestruturações só em declarações
+        Expr::Tupla(_) | Expr::Desestruturacao { .. } | Expr::Componente(_) => {
+            panic!("Expressão fora de um retorno ou declaração: {:?}", expr)
+        }
+
+        // Acesso a um campo de registro
+        Expr::Campo { nome, campo } => instrucao("mov rax, {}", operando_campo(nome, campo, ctx)),
+
+        // A construção de registros só aparece como valor inicial de variáveis
+        Expr::Registro { tipo, .. } => panic!("Construção de '{}' fora de uma declaração", tipo),
+
+        // Reserva de memória no heap
+        Expr::Alloc(tamanho) => {
+            let mut codigo = gerar_expr(tamanho, ctx);
+            codigo.push_str(&chamar_rotina(Rotina::Alloc, ctx));
+            codigo
+        }
+
+        // Endereço de uma variável: lea sobre o operando local ou global
+        Expr::Endereco(alvo) => gerar_endereco(alvo, ctx),
+
+        // Leitura do quadword apontado
+        Expr::Deref(ptr) => {
+            let mut codigo = gerar_expr(ptr, ctx);
+            codigo.push_str("mov rax, [rax]\n");
+            codigo
+        }
+
+        // Leitura de um inteiro da entrada padrão
+        Expr::Read => chamar_rotina(Rotina::Read, ctx),
+
+        // Acesso a um elemento de vetor
+        Expr::Indice { nome, indice } => {
+            let mut codigo = gerar_elemento(nome, indice, ctx);
+            codigo.push_str("mov rax, [rax]\n");
             codigo
         }
     }
 }
 
 // Gera código para um comando
-fn gerar_cmd(cmd: &Cmd, labels: &mut usize, deslocamentos: &HashMap<String, isize>) -> String {
+fn gerar_cmd(cmd: &Cmd, ctx: &mut Contexto) -> String {
     match cmd {
         // Atribuição de valor a uma variável
         Cmd::Atrib { nome, expr } => {
-            let mut codigo = gerar_expr(expr, labels, deslocamentos);
-            if let Some(offset) = deslocamentos.get(nome) {
-                let sinal = if *offset < 0 { "-" } else { "+" };
-                codigo.push_str(&format!("mov [rbp{}{}], rax\n", sinal, offset.abs()));
-            } else {
-                codigo.push_str(&format!("mov [{}], rax\n", nome));
+            let mut codigo = gerar_expr(expr, ctx);
+            codigo.push_str(&instrucao("mov {}, rax", operando(nome, ctx)));
+            codigo
+        }
+
+        // Atualização no lugar de uma variável, elemento, campo ou desreferência
+        Cmd::AtribComposta { alvo, operador, expr } => gerar_atrib_composta(alvo, operador, expr, ctx),
+
+        // Atribuição a um elemento de vetor
+        Cmd::AtribIndice { nome, indice, expr } => {
+            let mut codigo = gerar_expr(expr, ctx);
+            codigo.push_str("push rax\n"); // Salva o valor enquanto calcula o endereço
+            codigo.push_str(&gerar_elemento(nome, indice, ctx));
+            codigo.push_str("pop rbx\n");
+            codigo.push_str("mov [rax], rbx\n");
+            codigo
+        }
+
+        // Atribuição a um campo de registro
+        Cmd::AtribCampo { nome, campo, expr } => {
+            let mut codigo = gerar_expr(expr, ctx);
+            codigo.push_str(&instrucao("mov {}, rax", operando_campo(nome, campo, ctx)));
+            codigo
+        }
+
+        // Atribuição através de um ponteiro
+        Cmd::AtribDeref { ptr, expr } => {
+            let mut codigo = gerar_expr(expr, ctx);
+            codigo.push_str("push rax\n"); // Salva o valor enquanto calcula o endereço
+            codigo.push_str(&gerar_expr(ptr, ctx));
+            codigo.push_str("pop rbx\n");
+            codigo.push_str("mov [rax], rbx\n");
+            codigo
+        }
+
+        // Devolução de um bloco ao heap
+        Cmd::Free(ptr) => {
+            let mut codigo = gerar_expr(ptr, ctx);
+            codigo.push_str(&chamar_rotina(Rotina::Free, ctx));
+            codigo
+        }
+
+        // Assert: com a condição falsa, a rotina de erro escreve o arquivo, a linha
+        // e o texto da condição, montados em tempo de compilação
+        Cmd::Assert { cond, mensagem, linha, texto } => {
+            let l_ok = ctx.labels;
+            ctx.labels += 1;
+            let mut erro = format!("{}:{}: assert falhou: {}", ctx.arquivo, linha, texto);
+            if let Some(mensagem) = mensagem {
+                erro.push_str(&format!(": {}", mensagem));
+            }
+            erro.push('\n');
+
+            let mut codigo = gerar_expr(cond, ctx);
+            codigo.push_str("cmp rax, 0\n");
+            codigo.push_str(&format!("jne Lok{}\n", l_ok));
+            codigo.push_str(&format!("lea rsi, [_str{}]\n", indice_string(&erro, ctx)));
+            codigo.push_str(&format!("mov rdx, {}\n", erro.len()));
+            codigo.push_str("jmp _erro_assercao\n");
+            codigo.push_str(&format!("Lok{}:\n", l_ok));
+            ctx.usa_assert = true;
+            codigo
+        }
+
+        // Escrita de cada argumento: literais de string como texto, o resto como inteiro
+        Cmd::Print { args, tipos, nova_linha } => {
+            let mut codigo = String::new();
+            for (arg, tipo) in args.iter().zip(tipos) {
+                codigo.push_str(&gerar_expr(arg, ctx));
+                let rotina = match (arg, tipo) {
+                    (Expr::Str(_), _) => Rotina::PrintStr,
+                    (_, Tipo::U64) => Rotina::PrintUint,
+                    (_, Tipo::I64) => Rotina::PrintInt,
+                };
+                codigo.push_str(&chamar_escrita(rotina, ctx));
+            }
+            if *nova_linha {
+                codigo.push_str(&chamar_escrita(Rotina::NovaLinha, ctx));
             }
             codigo
         }
 
         // Comando if com else
         Cmd::If { cond, then_cmds, else_cmds } => {
-            let l_falso = *labels;
-            *labels += 1;
-            let l_fim = *labels;
-            *labels += 1;
+            let l_falso = ctx.labels;
+            ctx.labels += 1;
+            let l_fim = ctx.labels;
+            ctx.labels += 1;
 
-            let mut codigo = gerar_expr(cond, labels, deslocamentos);
+            let mut codigo = gerar_expr(cond, ctx);
             codigo.push_str("cmp rax, 0\n");
             codigo.push_str(&format!("je Lfalso{}\n", l_falso));
 
             for c in then_cmds {
-                codigo.push_str(&gerar_cmd(c, labels, deslocamentos));
+                codigo.push_str(&gerar_cmd(c, ctx));
             }
 
             codigo.push_str(&format!("jmp Lfim{}\n", l_fim));
             codigo.push_str(&format!("Lfalso{}:\n", l_falso));
 
             for c in else_cmds {
-                codigo.push_str(&gerar_cmd(c, labels, deslocamentos));
+                codigo.push_str(&gerar_cmd(c, ctx));
             }
 
             codigo.push_str(&format!("Lfim{}:\n", l_fim));
             codigo
         }
 
+        // Seleção por valor
+        Cmd::Match { valor, bracos, padrao } => gerar_match(valor, bracos, padrao.as_deref().unwrap_or_default(), ctx),
+
         // Laço while
         Cmd::While { cond, body } => {
-            let l_ini = *labels;
-            *labels += 1;
-            let l_fim = *labels;
-            *labels += 1;
+            let l_ini = ctx.labels;
+            ctx.labels += 1;
+            let l_fim = ctx.labels;
+            ctx.labels += 1;
 
             let mut codigo = format!("Linicio{}:\n", l_ini);
-            codigo.push_str(&gerar_expr(cond, labels, deslocamentos));
+            codigo.push_str(&gerar_expr(cond, ctx));
             codigo.push_str("cmp rax, 0\n");
             codigo.push_str(&format!("je Lfim{}\n", l_fim));
 
-            for c in body {
-                codigo.push_str(&gerar_cmd(c, labels, deslocamentos));
-            }
+            codigo.push_str(&gerar_corpo_laco(body, l_fim, ctx));
 
             codigo.push_str(&format!("jmp Linicio{}\n", l_ini));
             codigo.push_str(&format!("Lfim{}:\n", l_fim));
             codigo
         }
+
+        // Laço infinito: o único salto de volta é incondicional
+        Cmd::Loop { body } => {
+            let l_ini = ctx.labels;
+            ctx.labels += 1;
+            let l_fim = ctx.labels;
+            ctx.labels += 1;
+
+            let mut codigo = format!("Llaco{}:\n", l_ini);
+            codigo.push_str(&gerar_corpo_laco(body, l_fim, ctx));
+            codigo.push_str(&format!("jmp Llaco{}\n", l_ini));
+            codigo.push_str(&format!("Lfim{}:\n", l_fim));
+            codigo
+        }
+
+        // Laço do-while: a condição, testada depois do corpo, é o salto de volta
+        Cmd::DoWhile { body, cond } => {
+            let l_ini = ctx.labels;
+            ctx.labels += 1;
+            let l_fim = ctx.labels;
+            ctx.labels += 1;
+
+            let mut codigo = format!("Linicio{}:\n", l_ini);
+            codigo.push_str(&gerar_corpo_laco(body, l_fim, ctx));
+            codigo.push_str(&gerar_expr(cond, ctx));
+            codigo.push_str("cmp rax, 0\n");
+            codigo.push_str(&format!("jne Linicio{}\n", l_ini));
+            codigo.push_str(&format!("Lfim{}:\n", l_fim));
+            codigo
+        }
+
+        // Saída do laço mais interno
+        Cmd::Break => {
+            let l_fim = ctx.lacos.last().expect("break fora de laço rejeitado pelo parser");
+            format!("jmp Lfim{}\n", l_fim)
+        }
+
+        // Retorno antecipado: calcula o valor como o retorno final e salta para o epílogo
+        Cmd::Return(expr) => {
+            let mut codigo = match expr {
+                Some(expr) => gerar_retorno(expr, ctx),
+                None => "xor rax, rax\n".to_string(),
+            };
+            let l_retorno = match ctx.retorno {
+                Some(l) => l,
+                None => {
+                    ctx.labels += 1;
+                    *ctx.retorno.insert(ctx.labels - 1)
+                }
+            };
+            codigo.push_str(&format!("jmp Lretorno{}\n", l_retorno));
+            codigo
+        }
+
+        // Chamada usada como comando: o valor em rax é ignorado
+        Cmd::Expr(expr) => gerar_expr(expr, ctx),
+
+        // Assembly escrito no programa: as linhas vão como estão, com cada `{nome}`
+        // trocado pelo operando de memória da variável ou pelo valor da constante
+        Cmd::Asm { linhas, operandos } => {
+            let mut codigo = String::new();
+            for linha in linhas {
+                let mut linha = linha.clone();
+                for (operando, nome) in operandos {
+                    linha = linha.replace(&format!("{{{}}}", operando), &operando_asm(nome, ctx));
+                }
+                codigo.push_str(&linha);
+                codigo.push('\n');
+            }
+            codigo
+        }
+    }
+}
+
+// Operando de asm ligado a uma variável: `[rbp±n]` para locais, o label entre
+// colchetes para globais e o valor imediato para constantes
+fn operando_asm(nome: &str, ctx: &Contexto) -> String {
+    if let Ref::Constante(valor) = resolver(nome, ctx) {
+        return valor.to_string();
     }
+    let (prep, memoria) = operando(nome, ctx);
+    assert!(prep.is_empty(), "Operando de asm '{}' de uma função envolvente", nome);
+    memoria
 }
 
 //////////////
@@ -247,21 +1280,42 @@ fn gerar_cmd(cmd: &Cmd, labels: &mut usize, deslocamentos: &HashMap<String, isiz
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parser::{Expr, Cmd, Programa};
-    use std::collections::HashMap;
+    use crate::parser::{Expr, Cmd, Parser, Span};
+
+    // Gera o código de um programa fonte, depois da anotação de tipos, com as opções dadas
+    fn gerar_com(code: &str, opcoes: &Opcoes) -> String {
+        let mut prog = Parser::new(code).parse_programa().unwrap();
+        crate::tipos::anotar(&mut prog);
+        gerar_codigo(&prog, opcoes)
+    }
+
+    // Gera o código de um programa fonte com as opções padrão
+    fn gerar(code: &str) -> String {
+        gerar_com(code, &Opcoes::default())
+    }
 
     // Testa a expressão constante (um valor fixo)
     #[test]
     fn test_expr_const() {
-        let codigo = gerar_expr(&Expr::Const(42), &mut 0, &HashMap::new());
+        let codigo = gerar_expr(&Expr::Const(42), &mut Contexto::default());
         // Verifica se o código gerado move o valor constante para o registrador rax
         assert_eq!(codigo.trim(), "mov rax, 42");
     }
 
+    // Testa a escolha entre imediatos de 32 e de 64 bits
+    #[test]
+    fn test_imediato_64_bits() {
+        let mut ctx = Contexto::default();
+        assert_eq!(gerar_expr(&Expr::Const(-2147483648), &mut ctx), "mov rax, -2147483648\n");
+        assert_eq!(gerar_expr(&Expr::Const(1 << 32), &mut ctx), "mov rax, strict qword 4294967296\n");
+        ctx.constantes.insert("M".to_string(), i64::MIN);
+        assert_eq!(gerar_expr(&Expr::Var("M".to_string()), &mut ctx), format!("mov rax, strict qword {}\n", i64::MIN));
+    }
+
     // Testa a expressão com uma variável global
     #[test]
     fn test_expr_var_global() {
-        let codigo = gerar_expr(&Expr::Var("x".to_string()), &mut 0, &HashMap::new());
+        let codigo = gerar_expr(&Expr::Var("x".to_string()), &mut Contexto::default());
         // Verifica se o código gerado acessa a variável global "x"
         assert_eq!(codigo.trim(), "mov rax, [x]");
     }
@@ -269,9 +1323,10 @@ mod tests {
     // Testa a expressão com uma variável local (com offset)
     #[test]
     fn test_expr_var_local() {
-        let mut mapa = HashMap::new();
-        mapa.insert("x".to_string(), -8); // "x" está no endereço [rbp-8]
-        let codigo = gerar_expr(&Expr::Var("x".to_string()), &mut 0, &mapa);
+        let mut ctx = Contexto::default();
+        let local = Local { offset: -8, forma: Forma::Escalar }; // "x" está no endereço [rbp-8]
+        ctx.quadro.locais.insert("x".to_string(), local);
+        let codigo = gerar_expr(&Expr::Var("x".to_string()), &mut ctx);
         // Verifica se o código gerado acessa a variável local "x" com o offset correto
         assert_eq!(codigo.trim(), "mov rax, [rbp-8]");
     }
@@ -284,7 +1339,7 @@ mod tests {
             esq: Box::new(Expr::Const(2)),
             dir: Box::new(Expr::Const(3)),
         };
-        let codigo = gerar_expr(&expr, &mut 0, &HashMap::new());
+        let codigo = gerar_expr(&expr, &mut Contexto::default());
         // Verifica se o código gerado realiza a operação de adição
         assert!(codigo.contains("add rax, rbx"));
     }
@@ -296,7 +1351,7 @@ mod tests {
             nome: "x".to_string(),
             expr: Expr::Const(5),
         };
-        let codigo = gerar_cmd(&cmd, &mut 0, &HashMap::new());
+        let codigo = gerar_cmd(&cmd, &mut Contexto::default());
         // Verifica se o código gerado atribui o valor de rax à variável global "x"
         assert!(codigo.contains("mov [x], rax"));
     }
@@ -304,19 +1359,376 @@ mod tests {
     // Testa a geração de código de um programa mínimo
     #[test]
     fn test_gerar_codigo_minimal() {
-        let prog = Programa {
-            globais: vec![("x".to_string(), Expr::Const(1))],
-            funcoes: vec![], // Sem funções definidas
-            principal: vec![Cmd::Atrib {
-                nome: "x".to_string(),
-                expr: Expr::Const(2),
-            }],
-            retorno: Expr::Var("x".to_string()),
-        };
-        let codigo = gerar_codigo(&prog);
+        let codigo = gerar("var x = 1; main { x = 2; return x; }");
         // Verifica se o código gerado contém o início correto do programa e a atribuição para "x"
         assert!(codigo.contains("_start:"));
         assert!(codigo.contains("mov [x], rax"));
         assert!(codigo.contains("mov rdi, rax")); // Retorna o valor de "x"
     }
+
+    // Testa a reserva de um vetor global e a atribuição a um de seus elementos
+    #[test]
+    fn test_vetor_global() {
+        let codigo = gerar("var v[10]; main { v[3] = 7; return v[3]; }");
+        // Verifica a reserva de 10 quadwords e o cálculo do endereço do elemento
+        assert!(codigo.contains("v: resq 10"));
+        assert!(codigo.contains("lea rbx, [v]"));
+        assert!(codigo.contains("mov [rax], rbx"));
+        // Sem a opção, nenhuma verificação de limites é gerada
+        assert!(!codigo.contains("_erro_limites"));
+    }
+
+    // Testa a alocação de um vetor local na pilha
+    #[test]
+    fn test_vetor_local() {
+        let codigo = gerar("fun f() { var x = 0; var v[4]; return v[x]; } main { return f(); }");
+        // "x" ocupa [rbp-8] e os 4 elementos de "v" ficam entre [rbp-40] e [rbp-16]
+        assert!(codigo.contains("sub rsp, 40"));
+        assert!(codigo.contains("lea rbx, [rbp-40]"));
+    }
+
+    // Testa a verificação de limites opcional
+    #[test]
+    fn test_verificacao_limites() {
+        let mut ctx = Contexto { verificar_limites: true, ..Default::default() };
+        ctx.layout.globais.insert("v".to_string(), Forma::Vetor(5));
+        let expr = Expr::Indice { nome: "v".to_string(), indice: Box::new(Expr::Const(1)) };
+        let codigo = gerar_expr(&expr, &mut ctx);
+        // Verifica a comparação sem sinal com o tamanho do vetor
+        assert!(codigo.contains("cmp rax, 5\njae _erro_limites"));
+    }
+
+    // Testa a escrita de strings e inteiros com println
+    #[test]
+    fn test_cmd_println() {
+        let codigo = gerar(r#"main { println("oi", 7); return 0; }"#);
+        // Verifica o literal em .rodata e as chamadas ao runtime
+        assert!(codigo.contains("section .rodata\n_str0: db 111,105,0"));
+        assert!(codigo.contains("lea rax, [_str0]\ncall _rt_print_str"));
+        assert!(codigo.contains("mov rax, 7\ncall _rt_print_int"));
+        assert!(codigo.contains("call _rt_print_nl"));
+        // As rotinas usadas são emitidas no programa
+        assert!(codigo.contains("_rt_print_int:"));
+    }
+
+    // Testa a leitura de um inteiro da entrada padrão
+    #[test]
+    fn test_expr_read() {
+        let mut ctx = Contexto::default();
+        let codigo = gerar_expr(&Expr::Read, &mut ctx);
+        // Verifica a chamada e o registro da rotina do runtime
+        assert_eq!(codigo.trim(), "call _rt_read");
+        assert!(ctx.rotinas.contains(&Rotina::Read));
+    }
+
+    // Testa a inicialização e o acesso a campos de registros locais e globais
+    #[test]
+    fn test_registro() {
+        let code = "struct Point { x, y } var g = Point { y: 5 }; fun f() { var p = Point { y: 5 }; return p.y; } main { g.x = 1; return f(); }";
+        let codigo = gerar(code);
+        // O registro global tem valor constante e ocupa dois quadwords em .data
+        assert!(codigo.contains("section .data\ng: dq 0, 5\n"));
+        assert!(!codigo.contains("mov [g+8], rax"));
+        assert!(codigo.contains("mov [g], rax"));
+        // O registro local ocupa [rbp-16] (x) e [rbp-8] (y); x começa zerado
+        assert!(codigo.contains("sub rsp, 16"));
+        assert!(codigo.contains("xor rax, rax\nmov [rbp-16], rax"));
+        assert!(codigo.contains("mov rax, [rbp-8]\n"));
+    }
+
+    // Testa o endereço de variáveis locais e globais e a escrita por ponteiro
+    #[test]
+    fn test_ponteiros() {
+        let mut ctx = Contexto::default();
+        ctx.quadro.locais.insert("x".to_string(), Local { offset: -16, forma: Forma::Escalar });
+        let local = gerar_expr(&Expr::Endereco(Box::new(Expr::Var("x".to_string()))), &mut ctx);
+        let global = gerar_expr(&Expr::Endereco(Box::new(Expr::Var("g".to_string()))), &mut ctx);
+        assert_eq!(local.trim(), "lea rax, [rbp-16]");
+        assert_eq!(global.trim(), "lea rax, [g]");
+
+        let cmd = Cmd::AtribDeref { ptr: Expr::Var("x".to_string()), expr: Expr::Const(3) };
+        let codigo = gerar_cmd(&cmd, &mut ctx);
+        // O valor é guardado no endereço contido em "x"
+        assert!(codigo.contains("mov rax, [rbp-16]\npop rbx\nmov [rax], rbx"));
+    }
+
+    // Testa o acesso por índice a memória alocada no heap
+    #[test]
+    fn test_alloc_indice() {
+        let opcoes = Opcoes { verificar_limites: true, ..Default::default() };
+        let codigo = gerar_com("var p = alloc(80); main { p[9] = 1; free(p); return 0; }", &opcoes);
+        assert!(codigo.contains("mov rax, 80\ncall _rt_alloc\nmov [p], rax"));
+        // "p" é um ponteiro: o endereço base é o valor guardado e não há verificação de limites
+        assert!(codigo.contains("mov rax, 9\nmov rbx, [p]\nlea rax, [rbx+rax*8]"));
+        assert!(codigo.contains("call _rt_free"));
+        // O estado do heap é reservado uma única vez
+        assert_eq!(codigo.matches("_rt_heap_livre: resq 1").count(), 1);
+    }
+
+    // Testa o uso de funções como valores e a chamada indireta
+    #[test]
+    fn test_funcao_como_valor() {
+        let mut ctx = Contexto::default();
+        ctx.funcoes.insert("fib".to_string());
+        ctx.quadro.locais.insert("f".to_string(), Local { offset: -8, forma: Forma::Escalar });
+        // O nome da função vale o endereço do seu código
+        let valor = gerar_expr(&Expr::Var("fib".to_string()), &mut ctx);
+        assert_eq!(valor.trim(), "lea rax, [fib]");
+        let chamada = Expr::Call { nome: "f".to_string(), args: vec![Expr::Const(5)], nomeados: vec![], span: Span::default() };
+        let codigo = gerar_expr(&chamada, &mut ctx);
+        assert!(codigo.contains("push rax\nmov rax, [rbp-8]\ncall rax\nadd rsp, 8"));
+        // Chamadas pelo nome da função continuam diretas
+        ctx.parametros.insert("fib".to_string(), (vec![], HashMap::new()));
+        let direta = Expr::Call { nome: "fib".to_string(), args: vec![], nomeados: vec![], span: Span::default() };
+        let direta = gerar_expr(&direta, &mut ctx);
+        assert_eq!(direta.trim(), "call fib");
+    }
+
+    // Testa o acesso de funções aninhadas às variáveis das funções que as envolvem
+    #[test]
+    fn test_funcao_aninhada() {
+        let code = "fun f(n) { var s = 0; fun soma(x) { fun dobro() { return 2 * n; } s = s + x; return dobro(); } return soma(n); } main { return f(1); }";
+        let codigo = gerar(code);
+        // Funções aninhadas recebem um label prefixado pela função que as declarou
+        assert!(codigo.contains("\nf.soma:\n"));
+        assert!(codigo.contains("\nf.soma.dobro:\n"));
+        // f chama soma passando seu próprio rbp como link estático
+        assert!(codigo.contains("push rbp\ncall f.soma\nadd rsp, 16"));
+        // soma escreve em "s" de f através do link estático; seu parâmetro fica em rbp+24
+        assert!(codigo.contains("mov rax, [rbp+24]"));
+        assert!(codigo.contains("mov rcx, [rbp+16]\nmov [rcx-8], rax"));
+        // dobro lê "n" de f seguindo dois links estáticos
+        assert!(codigo.contains("mov rcx, [rbp+16]\nmov rcx, [rcx+16]\nmov rax, [rcx+16]"));
+    }
+
+    // Testa a substituição de constantes por imediatos
+    #[test]
+    fn test_constantes() {
+        let code = "const N = 10 * 4; const M = N + 2; var x = 0; main { x = M; return N; }";
+        let codigo = gerar(code);
+        assert!(codigo.contains("mov rax, 42\nmov [x], rax"));
+        assert!(codigo.contains("mov rax, 40\n"));
+        // Constantes não ocupam memória
+        assert!(!codigo.contains("N: resq") && !codigo.contains("[N]"));
+    }
+
+    // Testa a separação entre globais estáticas e a ordem das inicializações em _start
+    #[test]
+    fn test_globais_estaticas() {
+        let code = "const N = 3; fun dobro() { return 2 * b; } var a = dobro(); var b = read(); var c = N * 2; main { return a; }";
+        let codigo = gerar(code);
+        assert!(codigo.contains("section .data\nc: dq 6\n"));
+        assert!(codigo.contains("a: resq 1\nb: resq 1\n"));
+        // "a" chama dobro, que lê "b": "b" é inicializada primeiro
+        let b = codigo.find("mov [b], rax").unwrap();
+        let a = codigo.find("mov [a], rax").unwrap();
+        assert!(b < a);
+    }
+
+    // Testa as operações sem sinal escolhidas pela anotação de tipos
+    #[test]
+    fn test_operacoes_sem_sinal() {
+        let code = "var h: u64 = 0; var s = 0; main { h = h / 3; s = s >> 1; return h >> 2 < s; }";
+        let codigo = gerar(code);
+        assert!(codigo.contains("xor rdx, rdx\ndiv rbx\n"));
+        assert!(codigo.contains("sar rax, cl\n"));
+        assert!(codigo.contains("shr rax, cl\n"));
+        assert!(codigo.contains("setb cl\n"));
+        assert!(!codigo.contains("idiv"));
+    }
+
+    // Testa a escolha entre tabela de saltos e cadeia de comparações no match
+    #[test]
+    fn test_match() {
+        let denso = "var x = 0; main { match read() { 1 => { x = 1; } 2 | 3 => { x = 2; } 5 => { x = 3; } _ => { x = 0; } } return x; }";
+        let codigo = gerar(denso);
+        assert!(codigo.contains("sub rax, 1\ncmp rax, 4\nja Lpadrao"));
+        assert!(codigo.contains("jmp qword [rcx+rax*8]\n"));
+        // Uma entrada por valor entre 1 e 5: 2 e 3 dividem o braço, 4 vai para o `_`
+        let tabela = codigo.lines().find(|l| l.starts_with("_tabela")).unwrap();
+        let destinos: Vec<&str> = tabela.split_once("dq ").unwrap().1.split(',').collect();
+        assert_eq!(destinos.len(), 5);
+        assert_eq!(destinos[1], destinos[2]);
+        assert!(destinos[3].starts_with("Lpadrao"));
+
+        let esparso = "var x = 0; main { match read() { 1 => { x = 1; } 1000 => { x = 2; } _ => { } } return x; }";
+        let codigo = gerar(esparso);
+        assert!(codigo.contains("cmp rax, 1\nje Lcaso"));
+        assert!(codigo.contains("cmp rax, 1000\nje Lcaso"));
+        assert!(!codigo.contains("_tabela"));
+    }
+
+    // Testa a chamada de funções externas e o ponto de entrada main
+    #[test]
+    fn test_externas() {
+        let code = "extern fun printf(fmt, ...); var x = 0; main { x = printf(\"%d %d\", 1, 2); return 0; }";
+        let codigo = gerar(code);
+        assert!(codigo.contains("extern printf\nglobal main\n"));
+        assert!(codigo.contains("\nmain:\npush rbx\n"));
+        // O primeiro argumento, empilhado por último, vai para rdi; a pilha é alinhada para a chamada
+        assert!(codigo.contains("pop rdi\npop rsi\npop rdx\n"));
+        assert!(codigo.contains("and rsp, -16\nxor eax, eax\ncall printf\nmov rsp, r12\n"));
+        assert!(codigo.contains("pop rbx\nret\n"));
+        assert!(!codigo.contains("_start"));
+    }
+
+    // Testa que, com a libc, os buffers de printf são esvaziados antes de cada escrita direta
+    #[test]
+    fn test_escrita_com_libc() {
+        let code = "extern fun puts(s); var x = 0; main { x = puts(\"c\"); println(7); return 0; }";
+        let codigo = gerar(code);
+        assert!(codigo.contains("mov rax, 7\ncall _rt_descarrega\ncall _rt_print_int\ncall _rt_descarrega\ncall _rt_print_nl\n"));
+        assert!(codigo.contains("extern fflush\n"));
+        assert!(codigo.contains("call fflush wrt ..plt"));
+        // Sem a libc, print escreve direto
+        assert!(!gerar("main { println(7); return 0; }").contains("_rt_descarrega"));
+    }
+
+    // Testa os pontos de entrada exportados de uma biblioteca
+    #[test]
+    fn test_biblioteca() {
+        let code = "pub fun soma(a, b) { return a + b; } fun dobro(x) { var f = soma; return f(x, x); }";
+        let opcoes = Opcoes { tipo_saida: TipoSaida::BibliotecaEstatica, ..Default::default() };
+        let codigo = gerar_com(code, &opcoes);
+        assert!(codigo.starts_with("default rel\n"));
+        assert!(codigo.contains("global soma\n"));
+        assert!(!codigo.contains("global dobro") && !codigo.contains("_start") && !codigo.contains("main"));
+        // O corpo usa a convenção de Fun; o ponto de entrada empilha os registradores
+        assert!(codigo.contains("\n_fun_soma:\npush rbp\n"));
+        assert!(codigo.contains("\nsoma:\npush rbp\n"));
+        assert!(codigo.contains("push rsi\npush rdi\ncall _fun_soma\nadd rsp, 16\n"));
+        assert!(codigo.contains("lea rax, [_fun_soma]\n"));
+    }
+
+    // Testa o cabeçalho C das funções públicas
+    #[test]
+    fn test_cabecalho() {
+        let code = "pub fun media(a: u64, b: u64): u64 { return (a + b) / 2; } pub fun zero() { return 0; } pub fun limpa(p) { free(p); } fun aux() { return 1; }";
+        let prog = Parser::new(code).parse_programa().unwrap();
+        let cabecalho = gerar_cabecalho(&prog, "output");
+        assert!(cabecalho.starts_with("#ifndef OUTPUT_H\n#define OUTPUT_H\n\n#include <stdint.h>\n"));
+        assert!(cabecalho.contains("uint64_t media(uint64_t a, uint64_t b);\n"));
+        assert!(cabecalho.contains("int64_t zero(void);\n"));
+        assert!(cabecalho.contains("void limpa(int64_t p);\n"));
+        assert!(!cabecalho.contains("aux"));
+    }
+
+    // Testa a mensagem e o desvio para a rotina de erro do assert
+    #[test]
+    fn test_assert() {
+        let code = "var x = 0;\nmain {\n  x = read();\n  assert(x > 0, \"x positivo\");\n  return x;\n}";
+        let opcoes = Opcoes { arquivo: "texto.txt".into(), ..Default::default() };
+        let codigo = gerar_com(code, &opcoes);
+        let mensagem = "texto.txt:4: assert falhou: x > 0: x positivo\n";
+        let bytes: Vec<String> = mensagem.bytes().chain([0]).map(|b| b.to_string()).collect();
+        assert!(codigo.contains(&format!("_str0: db {}\n", bytes.join(","))));
+        assert!(codigo.contains("cmp rax, 0\njne Lok"));
+        assert!(codigo.contains(&format!("lea rsi, [_str0]\nmov rdx, {}\njmp _erro_assercao\n", mensagem.len())));
+        assert!(codigo.contains("\n_erro_assercao:\nmov rdi, 2\nmov rax, 1\nsyscall\nmov rdi, 201\n"));
+    }
+
+    // Testa tuplas devolvidas em rax e rdx e através do buffer reservado por quem chama
+    #[test]
+    fn test_tuplas() {
+        let code = "fun par(a) { return (a, a + 1); } fun trio(a) { return (a, 2, 3); } fun f() { var (x, y) = par(1); var (p, q, r) = trio(x); return p + r; } main { return f(); }";
+        let codigo = gerar(code);
+        // O segundo valor é calculado primeiro e fica em rdx
+        assert!(codigo.contains("push rax\nmov rax, [rbp+16]\npop rdx\n"));
+        assert!(codigo.contains("call par\nadd rsp, 8\nmov [rbp-8], rax\nmov [rbp-16], rdx\n"));
+        // trio recebe o endereço do buffer em rbp+16 e o parâmetro em rbp+24
+        assert!(codigo.contains("mov rax, 2\nmov rbx, [rbp+16]\nmov [rbx+8], rax\n"));
+        // f reserva o buffer na pilha e passa o seu endereço depois do argumento
+        assert!(codigo.contains("sub rsp, 24\n"));
+        assert!(codigo.contains("lea rax, [rsp+8]\npush rax\ncall trio\n"));
+        assert!(codigo.contains("pop rax\nmov [rbp-24], rax\n"));
+    }
+
+    // Testa os laços loop e do-while, o break e o retorno antecipado
+    #[test]
+    fn test_lacos() {
+        let code = "fun f(n) { loop { n = n - 1; if n < 3 { break; } else { } } do { n = n + 2; } while n < 10; return n; } fun g(n) { while 1 { return n; } return 0; } main { return f(5) + g(1); }";
+        let codigo = gerar(code);
+        // O loop volta ao início com um salto incondicional e o break sai dele
+        assert!(codigo.contains("jmp Llaco"));
+        assert!(codigo.contains("jmp Lfim"));
+        // O do-while testa a condição depois do corpo
+        assert!(codigo.contains("setl cl\nmov rax, rcx\ncmp rax, 0\njne Linicio"));
+        // O return antecipado salta para o epílogo, depois do retorno final
+        assert!(codigo.contains("mov rax, [rbp+16]\njmp Lretorno"));
+        assert!(codigo.contains("mov rax, 0\nLretorno"));
+    }
+
+    // Testa as atribuições compostas feitas direto na memória
+    #[test]
+    fn test_atrib_composta() {
+        let code = "const K = 3; var g = 0; var v[4]; fun f(n) { var x = 0; x++; n -= K; g += n; v[n] *= 5; x %= n; return x; } main { return f(1); }";
+        let codigo = gerar(code);
+        assert!(codigo.contains("add qword [rbp-8], 1\n"));
+        assert!(codigo.contains("sub qword [rbp+16], 3\n"));
+        assert!(codigo.contains("mov rax, [rbp+16]\npush rax\npop rbx\nadd [g], rbx\n"));
+        assert!(codigo.contains("lea rax, [rbx+rax*8]\nmov rcx, rax\nimul rax, qword [rcx], 5\nmov [rcx], rax\n"));
+        assert!(codigo.contains("pop rbx\nmov rax, [rbp-8]\ncqo\nidiv rbx\nmov [rbp-8], rdx\n"));
+
+        // Com u64, a divisão no lugar é sem sinal
+        let codigo = gerar("var h: u64 = 9; main { h /= 2; return 0; }");
+        assert!(codigo.contains("mov rbx, 2\nmov rax, [h]\nxor rdx, rdx\ndiv rbx\nmov [h], rax\n"));
+    }
+
+    // Testa o if como expressão: cmov com valores sem efeitos, desvios nos demais casos
+    #[test]
+    fn test_condicional() {
+        let code = "var a = 0; var b = 0; main { a = if b > 0 { b } else { 0 - b }; b = if a { read() } else { 7 }; return a; }";
+        let codigo = gerar(code);
+        let cmov = "setg cl\nmov rax, rcx\ncmp rax, 0\npop rax\npop rbx\ncmove rax, rbx\nmov [a], rax\n";
+        assert!(codigo.contains(cmov));
+        // Com uma leitura, só o valor escolhido é calculado, entre desvios
+        assert!(codigo.contains("mov rax, [a]\ncmp rax, 0\nje Lfalso"));
+        assert!(codigo.contains("call _rt_read\njmp Lfim"));
+        assert!(!codigo.contains("cmove rax, rbx\nmov [b], rax"));
+    }
+
+    // Testa a troca dos operandos do asm por locais, globais e constantes
+    #[test]
+    fn test_asm() {
+        let code = r#"const N = 60; var g = 0; fun f(x) { asm { "mov rax, {n}", "add rax, {x}", "mov {g}, rax", "vmovdqa64 zmm0{k1}{z}, zmm1", n = N, x = x, g = g } return g; } main { return f(1); }"#;
+        let codigo = gerar(code);
+        assert!(codigo.contains("mov rax, 60\nadd rax, [rbp+16]\nmov [g], rax\nvmovdqa64 zmm0{k1}{z}, zmm1\n"));
+    }
+
+    // Testa a escolha da rotina de escrita pelo tipo do valor
+    #[test]
+    fn test_print_sem_sinal() {
+        let code = "var h: u64 = 1; var s = 1; main { println(h, s, 0xFFFF_FFFF_FFFF_FFFF); return 0; }";
+        let codigo = gerar(code);
+        assert!(codigo.contains("mov rax, [h]\ncall _rt_print_uint\n"));
+        assert!(codigo.contains("mov rax, [s]\ncall _rt_print_int\n"));
+        assert_eq!(codigo.matches("call _rt_print_uint").count(), 2);
+        assert!(codigo.contains("_rt_print_uint:\n"));
+    }
+
+    // Testa que o endereço de uma função pública é o do corpo, não o do ponto de entrada para C
+    #[test]
+    fn test_endereco_exportada() {
+        let code = "pub fun f(x) { return x; } fun g() { var p = &f; var q = f; return p(1) + q(2); }";
+        let opcoes = Opcoes { tipo_saida: TipoSaida::Objeto, ..Opcoes::default() };
+        let codigo = gerar_com(code, &opcoes);
+        assert_eq!(codigo.matches("lea rax, [_fun_f]\n").count(), 2);
+        assert!(!codigo.contains("lea rax, [f]"));
+    }
+
+    // Testa o preenchimento dos argumentos nomeados e padrão na ordem dos parâmetros
+    #[test]
+    fn test_argumentos_nomeados() {
+        let code = "fun range(lo, hi, passo = 3) { return hi - lo; } main { return range(hi = 9, lo = 4); }";
+        let codigo = gerar(code);
+        assert!(codigo.contains("mov rax, 3\npush rax\nmov rax, 9\npush rax\nmov rax, 4\npush rax\ncall range\nadd rsp, 24\n"));
+    }
+
+    // Testa que o valor padrão é a constante, mesmo com uma local de mesmo nome em quem chama
+    #[test]
+    fn test_padrao_escondido() {
+        let code = "const UM = 1; fun range(lo, hi, passo = UM) { return hi - lo; } fun g() { var UM = 5; return range(0, 10); } main { return g(); }";
+        let codigo = gerar(code);
+        assert!(codigo.contains("mov rax, 1\npush rax\nmov rax, 10\npush rax\nmov rax, 0\npush rax\ncall range\n"));
+    }
 }
@@ -0,0 +1,124 @@
+// Importa os tipos definidos no módulo de parser
+use crate::parser::{Expr, Programa};
+// Importa o HashMap da biblioteca padrão
+use std::collections::HashMap;
+
+// Avalia as constantes do programa na ordem em que foram declaradas;
+// cada constante pode usar as declaradas antes dela
+pub fn avaliar_constantes(prog: &Programa) -> Result<HashMap<String, i64>, String> {
+    let mut valores = HashMap::new();
+    for (nome, expr) in &prog.constantes {
+        let valor = avaliar(expr, &valores).map_err(|e| format!("Constante '{}': {}", nome, e))?;
+        if valores.insert(nome.clone(), valor).is_some() {
+            return Err(format!("Constante '{}' declarada mais de uma vez", nome));
+        }
+    }
+    Ok(valores)
+}
+
+// Avalia uma expressão em tempo de compilação, com a mesma aritmética de 64 bits
+// do código gerado. Só são aceitos literais, constantes e operações entre eles.
+pub fn avaliar(expr: &Expr, constantes: &HashMap<String, i64>) -> Result<i64, String> {
+    match expr {
+        Expr::Const(n) => Ok(*n),
+        Expr::Var(nome) => constantes
+            .get(nome)
+            .copied()
+            .ok_or_else(|| format!("'{}' não é uma constante", nome)),
+        Expr::OpBin { operador, esq, dir } => {
+            let a = avaliar(esq, constantes)?;
+            let b = avaliar(dir, constantes)?;
+            match operador.as_str() {
+                "+" => Ok(a.wrapping_add(b)),
+                "-" => Ok(a.wrapping_sub(b)),
+                "*" => Ok(a.wrapping_mul(b)),
+                "/" => a.checked_div(b).ok_or_else(|| "divisão por zero ou fora do intervalo".into()),
+                "%" => a.checked_rem(b).ok_or_else(|| "divisão por zero ou fora do intervalo".into()),
+                "<<" => Ok(a.wrapping_shl(b as u32)),
+                ">>" => Ok(a.wrapping_shr(b as u32)),
+                "==" => Ok((a == b) as i64),
+                "<" => Ok((a < b) as i64),
+                ">" => Ok((a > b) as i64),
+                // Versões sem sinal, escolhidas na anotação de tipos para operandos u64
+                "u/" => (a as u64).checked_div(b as u64).map(|v| v as i64).ok_or_else(|| "divisão por zero".into()),
+                "u%" => (a as u64).checked_rem(b as u64).map(|v| v as i64).ok_or_else(|| "divisão por zero".into()),
+                "u>>" => Ok((a as u64).wrapping_shr(b as u32) as i64),
+                "u<" => Ok(((a as u64) < b as u64) as i64),
+                "u>" => Ok((a as u64 > b as u64) as i64),
+                _ => Err(format!("operador '{}' não suportado", operador)),
+            }
+        }
+        Expr::Condicional { cond, entao, senao } => match avaliar(cond, constantes)? {
+            0 => avaliar(senao, constantes),
+            _ => avaliar(entao, constantes),
+        },
+        _ => Err("depende de um valor calculado em tempo de execução".into()),
+    }
+}
+
+// Valor inicial de uma global, se for calculável em tempo de compilação:
+// um quadword para escalares e um por campo para registros (campos omitidos valem 0)
+pub fn valor_estatico(
+    expr: &Expr,
+    constantes: &HashMap<String, i64>,
+    registros: &HashMap<String, Vec<String>>,
+) -> Option<Vec<i64>> {
+    match expr {
+        Expr::Registro { tipo, campos } => registros[tipo]
+            .iter()
+            .map(|campo| match campos.iter().find(|(c, _)| c == campo) {
+                Some((_, valor)) => avaliar(valor, constantes).ok(),
+                None => Some(0),
+            })
+            .collect(),
+        _ => avaliar(expr, constantes).ok().map(|valor| vec![valor]),
+    }
+}
+
+//////////////
+/// TESTES ///
+//////////////
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    // Avalia as constantes do programa fonte
+    fn constantes(fonte: &str) -> Result<HashMap<String, i64>, String> {
+        let prog = Parser::new(fonte).parse_programa().expect("programa válido");
+        avaliar_constantes(&prog)
+    }
+
+    // Testa constantes que usam outras constantes
+    #[test]
+    fn test_avaliar_constantes() {
+        let valores = constantes("const N = 10 * 4; const M = N / 3 - 1; main { return M; }").unwrap();
+        assert_eq!(valores["N"], 40);
+        assert_eq!(valores["M"], 12);
+        assert_eq!(constantes("const R = 17 % 5; main { return R; }").unwrap()["R"], 2);
+        let condicional = constantes("const N = 4; const P = if N > 3 { N * 2 } else { 0 }; main { return P; }");
+        assert_eq!(condicional.unwrap()["P"], 8);
+    }
+
+    // Testa constantes que dependem de valores de tempo de execução
+    #[test]
+    fn test_constante_em_execucao() {
+        let global = constantes("var x = 1; const N = x + 1; main { return N; }").unwrap_err();
+        assert_eq!(global, "Constante 'N': 'x' não é uma constante");
+        let leitura = constantes("const N = read(); main { return N; }").unwrap_err();
+        assert!(leitura.contains("tempo de execução"), "{}", leitura);
+        assert!(constantes("const N = 1 / 0; main { return N; }").is_err());
+    }
+
+    // Testa a avaliação sem sinal das constantes u64, depois da anotação de tipos
+    #[test]
+    fn test_constantes_sem_sinal() {
+        let mut prog = Parser::new("const M = 0xFFFF_FFFF_FFFF_FFFF >> 1; const D = 0xFFFF_FFFF_FFFF_FFFE / 2; main { return 0; }")
+            .parse_programa()
+            .unwrap();
+        crate::tipos::anotar(&mut prog);
+        let valores = avaliar_constantes(&prog).unwrap();
+        assert_eq!(valores["M"], i64::MAX);
+        assert_eq!(valores["D"], i64::MAX);
+    }
+}
@@ -0,0 +1,240 @@
+// Importa os tipos definidos no módulo de parser
+use crate::parser::{Cmd, Expr, FunDecl, Programa};
+// Importa o HashMap da biblioteca padrão
+use std::collections::HashMap;
+
+// Forma de armazenamento de uma variável
+#[derive(Debug, PartialEq, Clone)]
+pub enum Forma {
+    Escalar,          // Um único quadword
+    Vetor(usize),     // Quadwords consecutivos, um por elemento
+    Registro(String), // Campos do registro com esse nome, um quadword cada
+}
+
+// Variável da função atual: offset do menor endereço em relação a rbp e sua forma
+#[derive(Debug, PartialEq, Clone)]
+pub struct Local {
+    pub offset: isize,
+    pub forma: Forma,
+}
+
+// Quadro de pilha de uma função
+#[derive(Debug, Default, Clone)]
+pub struct Quadro {
+    pub locais: HashMap<String, Local>, // Parâmetros e variáveis locais
+    pub tamanho: usize,                 // Bytes reservados abaixo de rbp
+    pub buffer_retorno: Option<isize>,  // Offset do endereço onde a função escreve a tupla devolvida
+}
+
+// Número máximo de valores devolvidos em registradores (rax e rdx); tuplas maiores
+// são escritas em um buffer reservado por quem chama
+pub const MAX_VALORES_REGISTRADORES: usize = 2;
+
+// Número de valores devolvidos por uma função: o tamanho da tupla, 1 ou 0 para procedimentos.
+// Sem o return final, vale o primeiro `return e;` do corpo, como em laços encerrados por return.
+pub fn valores_retorno(func: &FunDecl) -> usize {
+    match func.retorno.as_ref().or_else(|| retorno_no_corpo(&func.comandos)) {
+        Some(Expr::Tupla(valores)) => valores.len(),
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+// Primeiro valor devolvido por um return dentro dos comandos
+fn retorno_no_corpo(cmds: &[Cmd]) -> Option<&Expr> {
+    cmds.iter().find_map(|cmd| match cmd {
+        Cmd::Return(valor) => valor.as_ref(),
+        Cmd::If { then_cmds, else_cmds, .. } => retorno_no_corpo(then_cmds).or_else(|| retorno_no_corpo(else_cmds)),
+        Cmd::While { body, .. } | Cmd::Loop { body } | Cmd::DoWhile { body, .. } => retorno_no_corpo(body),
+        Cmd::Match { bracos, padrao, .. } => bracos
+            .iter()
+            .find_map(|(_, cmds)| retorno_no_corpo(cmds))
+            .or_else(|| padrao.as_deref().and_then(retorno_no_corpo)),
+        _ => None,
+    })
+}
+
+// Layout dos dados do programa: registros e variáveis globais
+#[derive(Debug, Default, Clone)]
+pub struct Layout {
+    pub registros: HashMap<String, Vec<String>>, // Campos de cada registro, na ordem declarada
+    pub globais: HashMap<String, Forma>,         // Forma de cada variável global
+}
+
+// Forma de uma variável declarada com esse valor inicial
+pub fn forma_inicial(expr: &Expr) -> Forma {
+    match expr {
+        Expr::Registro { tipo, .. } => Forma::Registro(tipo.clone()),
+        _ => Forma::Escalar,
+    }
+}
+
+impl Layout {
+    // Calcula o layout a partir das declarações do programa
+    pub fn new(prog: &Programa) -> Self {
+        let registros = prog
+            .registros
+            .iter()
+            .map(|r| (r.nome.clone(), r.campos.clone()))
+            .collect();
+        let mut globais: HashMap<String, Forma> = prog
+            .globais
+            .iter()
+            .map(|(nome, expr)| (nome.clone(), forma_inicial(expr)))
+            .collect();
+        for (nome, tamanho) in &prog.vetores {
+            globais.insert(nome.clone(), Forma::Vetor(*tamanho));
+        }
+        Layout { registros, globais }
+    }
+
+    // Tamanho em bytes ocupado por uma variável com essa forma
+    pub fn tamanho(&self, forma: &Forma) -> usize {
+        match forma {
+            Forma::Escalar => 8,
+            Forma::Vetor(n) => 8 * n,
+            Forma::Registro(tipo) => 8 * self.registros.get(tipo).map_or(0, |c| c.len()),
+        }
+    }
+
+    // Offset em bytes de um campo dentro do registro
+    pub fn offset_campo(&self, tipo: &str, campo: &str) -> Option<isize> {
+        let campos = self.registros.get(tipo)?;
+        campos.iter().position(|c| c == campo).map(|i| 8 * i as isize)
+    }
+
+    // Calcula o quadro de pilha de uma função: parâmetros acima de rbp
+    // (rbp+16, rbp+24, ...) e variáveis locais abaixo, na ordem declarada.
+    // Funções aninhadas recebem em rbp+16 o rbp da função que as declarou
+    // (o link estático), e seus parâmetros começam em rbp+24.
+    pub fn quadro(&self, func: &FunDecl, aninhada: bool) -> Quadro {
+        let mut locais = HashMap::new();
+
+        // Parâmetros começam depois do rbp, do endereço de retorno, do link estático e
+        // do endereço do buffer de retorno
+        let mut offset = if aninhada { 24 } else { 16 };
+        let mut buffer_retorno = None;
+        if valores_retorno(func) > MAX_VALORES_REGISTRADORES {
+            buffer_retorno = Some(offset);
+            offset += 8;
+        }
+        for param in &func.parametros {
+            locais.insert(param.clone(), Local { offset, forma: Forma::Escalar });
+            offset += 8;
+        }
+
+        let formas = func
+            .variaveis
+            .iter()
+            .map(|(nome, expr)| (nome, forma_inicial(expr)))
+            .chain(func.vetores.iter().map(|(nome, n)| (nome, Forma::Vetor(*n))));
+
+        let mut tamanho = 0;
+        for (nome, forma) in formas {
+            tamanho += self.tamanho(&forma);
+            locais.insert(nome.clone(), Local { offset: -(tamanho as isize), forma });
+        }
+
+        Quadro { locais, tamanho, buffer_retorno }
+    }
+}
+
+//////////////
+/// TESTES ///
+//////////////
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{FunDecl, RegistroDecl, Tipo};
+
+    // Cria um layout com o registro Point { x, y }
+    fn layout_point() -> Layout {
+        let mut layout = Layout::default();
+        layout.registros.insert("Point".into(), vec!["x".into(), "y".into()]);
+        layout
+    }
+
+    // Testa o tamanho e os offsets dos campos de um registro
+    #[test]
+    fn test_offset_campo() {
+        let layout = layout_point();
+        assert_eq!(layout.tamanho(&Forma::Registro("Point".into())), 16);
+        assert_eq!(layout.offset_campo("Point", "y"), Some(8));
+        assert_eq!(layout.offset_campo("Point", "z"), None);
+    }
+
+    // Testa o quadro de uma função com escalares, registro e vetor
+    #[test]
+    fn test_quadro() {
+        let layout = layout_point();
+        let func = FunDecl {
+            nome: "f".into(),
+            parametros: vec!["a".into()],
+            padroes: HashMap::new(),
+            tipos: HashMap::new(),
+            tipo_retorno: Tipo::I64,
+            variaveis: vec![
+                ("x".into(), Expr::Const(0)),
+                ("p".into(), Expr::Registro { tipo: "Point".into(), campos: vec![] }),
+            ],
+            vetores: vec![("v".into(), 3)],
+            aninhadas: vec![],
+            comandos: vec![],
+            retorno: Some(Expr::Const(0)),
+            publica: false,
+        };
+        let quadro = layout.quadro(&func, false);
+        // 8 bytes de "x", 16 de "p" e 24 de "v"
+        assert_eq!(quadro.tamanho, 48);
+        assert_eq!(quadro.locais["a"].offset, 16);
+        assert_eq!(quadro.locais["x"].offset, -8);
+        assert_eq!(quadro.locais["p"], Local { offset: -24, forma: Forma::Registro("Point".into()) });
+        assert_eq!(quadro.locais["v"].offset, -48);
+        // Em uma função aninhada o link estático ocupa rbp+16
+        assert_eq!(layout.quadro(&func, true).locais["a"].offset, 24);
+        assert_eq!(quadro.buffer_retorno, None);
+    }
+
+    // Testa o endereço do buffer de uma função que devolve uma tupla grande
+    #[test]
+    fn test_quadro_tupla() {
+        let func = FunDecl {
+            nome: "f".into(),
+            parametros: vec!["a".into()],
+            padroes: HashMap::new(),
+            tipos: HashMap::new(),
+            tipo_retorno: Tipo::I64,
+            variaveis: vec![],
+            vetores: vec![],
+            aninhadas: vec![],
+            comandos: vec![],
+            retorno: Some(Expr::Tupla(vec![Expr::Const(1), Expr::Const(2), Expr::Const(3)])),
+            publica: false,
+        };
+        assert_eq!(valores_retorno(&func), 3);
+        let quadro = Layout::default().quadro(&func, true);
+        assert_eq!(quadro.buffer_retorno, Some(24));
+        assert_eq!(quadro.locais["a"].offset, 32);
+    }
+
+    // Testa a forma das variáveis globais
+    #[test]
+    fn test_globais() {
+        let prog = Programa {
+            registros: vec![RegistroDecl { nome: "Point".into(), campos: vec!["x".into(), "y".into()] }],
+            enums: vec![],
+            constantes: vec![],
+            globais: vec![("p".into(), Expr::Registro { tipo: "Point".into(), campos: vec![] })],
+            tipos: HashMap::new(),
+            vetores: vec![("v".into(), 4)],
+            funcoes: vec![],
+            externas: vec![],
+            principal: vec![],
+            retorno: Expr::Const(0),
+            tem_principal: true,
+        };
+        let layout = Layout::new(&prog);
+        assert_eq!(layout.globais["p"], Forma::Registro("Point".into()));
+        assert_eq!(layout.tamanho(&layout.globais["v"]), 32);
+    }
+}
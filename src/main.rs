@@ -5,10 +5,19 @@ use std::io::{self, Read, Write};
 use std::process::{self, Command};
 
 mod parser;
+mod checker;
 mod codegen;
+mod consteval;
+mod layout;
+mod runtime;
+mod tipos;
 
+use codegen::{Opcoes, TipoSaida};
 use parser::Parser;
 
+/// Arquivo com o código fonte do programa
+const ARQUIVO_FONTE: &str = "texto.txt";
+
 /// Lê o conteúdo de um arquivo e o retorna como `String`.
 fn read_file(filename: &str) -> io::Result<String> {
     let mut file = File::open(filename)?; // Corrigido para usar `filename` como argumento
@@ -31,9 +40,52 @@ fn run_command(command: &str, args: &[&str]) -> process::ExitStatus {
     status
 }
 
+/// O que o compilador produz: o programa compilado ou, com `--emit ast`, a árvore sintática
+#[derive(Debug, Default, PartialEq)]
+enum Emissao {
+    #[default]
+    Codigo,
+    Ast,
+}
+
+/// Interpreta os argumentos de linha de comando
+fn ler_opcoes(args: &[String]) -> Result<(Opcoes, Emissao), String> {
+    let mut opcoes = Opcoes::default();
+    let mut emissao = Emissao::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bounds-check" => opcoes.verificar_limites = true,
+            "--emit" => match args.next().map(String::as_str) {
+                Some("ast") => emissao = Emissao::Ast,
+                _ => return Err("Opção --emit espera 'ast'".into()),
+            },
+            "--crate-type=bin" => opcoes.tipo_saida = TipoSaida::Executavel,
+            "--crate-type=staticlib" => opcoes.tipo_saida = TipoSaida::BibliotecaEstatica,
+            "--crate-type=object" => opcoes.tipo_saida = TipoSaida::Objeto,
+            _ => return Err(format!("Opção desconhecida: {}", arg)),
+        }
+    }
+    Ok((opcoes, emissao))
+}
+
+/// Escreve em "output.h" os protótipos C das funções públicas
+fn escrever_cabecalho(prog: &parser::Programa) {
+    let cabecalho = codegen::gerar_cabecalho(prog, "output");
+    let mut file = File::create("output.h").expect("Erro ao criar output.h");
+    file.write_all(cabecalho.as_bytes()).expect("Erro ao escrever no arquivo");
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (mut opcoes, emissao) = ler_opcoes(&args).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+    opcoes.arquivo = ARQUIVO_FONTE.to_string();
+
     // Lê o conteúdo do arquivo de entrada
-    let input = read_file("texto.txt").unwrap_or_else(|_| {
+    let input = read_file(ARQUIVO_FONTE).unwrap_or_else(|_| {
         eprintln!("Erro ao abrir o arquivo");
         process::exit(1);
     });
@@ -41,9 +93,31 @@ fn main() {
     // Cria o parser e tenta processar o programa
     let mut parser = Parser::new(&input);
     match parser.parse_programa() {
-        Ok(prog) => {
+        Ok(mut prog) => {
+            // Escolhe as operações com ou sem sinal conforme os tipos; as constantes e os
+            // padrões do match avaliados na verificação já usam as operações escolhidas
+            tipos::anotar(&mut prog);
+
+            // Verifica nomes e chamadas antes de gerar o código
+            if let Err(err) = checker::verificar(&prog) {
+                eprintln!("Erro semântico: {}", err);
+                process::exit(1);
+            }
+            // Mostra a árvore sintática anotada, com os valores das variantes dos enums;
+            // não depende do tipo de saída, então funciona também para bibliotecas sem main
+            if emissao == Emissao::Ast {
+                println!("{:#?}", prog);
+                return;
+            }
+
+            let biblioteca = opcoes.tipo_saida != TipoSaida::Executavel;
+            if let Err(err) = checker::verificar_saida(&prog, biblioteca) {
+                eprintln!("Erro semântico: {}", err);
+                process::exit(1);
+            }
+
             // Gera o código assembly a partir da estrutura do programa
-            let codigo = codegen::gerar_codigo(&prog);
+            let codigo = codegen::gerar_codigo(&prog, &opcoes);
 
             // Escreve o código assembly gerado em "output.asm"
             let mut file = File::create("output.asm").expect("Erro ao criar output.asm");
@@ -54,9 +128,27 @@ fn main() {
             // Executa os comandos para compilar o código assembly
             println!("Compilando o código assembly...");
             run_command("nasm", &["-f", "elf64", "output.asm", "-o", "output.o"]);
-            run_command("ld", &["output.o", "-o", "prog"]);
-
-            println!("Executável gerado com sucesso: prog");
+            match opcoes.tipo_saida {
+                TipoSaida::Executavel if prog.externas.is_empty() => {
+                    run_command("ld", &["output.o", "-o", "prog"]);
+                    println!("Executável gerado com sucesso: prog");
+                }
+                TipoSaida::Executavel => {
+                    // Funções externas vêm da libc ou de outros objetos ligados pelo cc;
+                    // o código gerado usa endereços absolutos, então o executável não é PIE
+                    run_command("cc", &["-no-pie", "output.o", "-o", "prog"]);
+                    println!("Executável gerado com sucesso: prog");
+                }
+                TipoSaida::Objeto => {
+                    escrever_cabecalho(&prog);
+                    println!("Objeto gerado com sucesso: output.o (protótipos em output.h)");
+                }
+                TipoSaida::BibliotecaEstatica => {
+                    run_command("ar", &["rcs", "liboutput.a", "output.o"]);
+                    escrever_cabecalho(&prog);
+                    println!("Biblioteca gerada com sucesso: liboutput.a (protótipos em output.h)");
+                }
+            }
         }
         Err(err) => {
             // Exibe erro de parsing se houver
@@ -110,4 +202,21 @@ mod tests {
         let result = parser.parse_programa();
         assert!(result.is_err(), "Era esperado erro de parsing");
     }
+
+    /// Testa a leitura das opções de linha de comando
+    #[test]
+    fn test_ler_opcoes() {
+        let (opcoes, emissao) = ler_opcoes(&["--bounds-check".to_string()]).unwrap();
+        assert!(opcoes.verificar_limites);
+        assert_eq!(emissao, Emissao::Codigo);
+        assert!(!ler_opcoes(&[]).unwrap().0.verificar_limites);
+        assert!(ler_opcoes(&["--xyz".to_string()]).is_err());
+        let (biblioteca, _) = ler_opcoes(&["--crate-type=staticlib".to_string()]).unwrap();
+        assert_eq!(biblioteca.tipo_saida, TipoSaida::BibliotecaEstatica);
+        assert_eq!(ler_opcoes(&[]).unwrap().0.tipo_saida, TipoSaida::Executavel);
+        assert!(ler_opcoes(&["--crate-type=dylib".to_string()]).is_err());
+        let args = ["--emit".to_string(), "ast".to_string()];
+        assert_eq!(ler_opcoes(&args).unwrap().1, Emissao::Ast);
+        assert!(ler_opcoes(&["--emit".to_string()]).is_err());
+    }
 }
@@ -2,11 +2,43 @@
 use std::iter::Peekable;
 // Importa o tipo que representa uma sequência de caracteres
 use std::str::Chars;
+// Importa o HashMap da biblioteca padrão
+use std::collections::HashMap;
+// Importa a formatação usada para mostrar trechos do código
+use std::fmt;
+
+// Trecho do código fonte: linha e coluna (a partir de 1) do primeiro e do último caractere
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub inicio: (usize, usize),
+    pub fim: (usize, usize),
+}
+
+// Mostra o trecho como `linha:coluna-coluna` ou, em mais de uma linha, `linha:coluna-linha:coluna`
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ((linha, coluna), (linha_fim, coluna_fim)) = (self.inicio, self.fim);
+        if linha == linha_fim {
+            write!(f, "{}:{}-{}", linha, coluna, coluna_fim)
+        } else {
+            write!(f, "{}:{}-{}:{}", linha, coluna, linha_fim, coluna_fim)
+        }
+    }
+}
+
+// Tipos inteiros da linguagem
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Tipo {
+    #[default]
+    I64, // Inteiro com sinal (tipo de variáveis sem anotação)
+    U64, // Inteiro sem sinal
+}
 
 // Enumeração que representa uma expressão
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expr {
-    Const(i32), // Constante inteira
+    Const(i64), // Constante inteira
+    Str(String), // Literal de string
     Var(String), // Variável com nome
     OpBin {
         operador: String,       // Operador binário como "+", "*", etc.
@@ -15,7 +47,36 @@ pub enum Expr {
     },
     Call {
         nome: String,           // Nome da função
-        args: Vec<Expr>,        // Argumentos da função
+        args: Vec<Expr>,        // Argumentos passados pela posição
+        nomeados: Vec<(String, Expr)>, // Argumentos passados pelo nome do parâmetro (passo = 2)
+        span: Span,             // Trecho da chamada, citado nos erros
+    },
+    Indice {
+        nome: String,           // Nome do vetor
+        indice: Box<Expr>,      // Posição acessada
+    },
+    Read,                       // Leitura de um inteiro da entrada padrão
+    Registro {
+        tipo: String,                 // Nome do registro construído
+        campos: Vec<(String, Expr)>,  // Valores dos campos informados
+    },
+    Campo {
+        nome: String,           // Variável do tipo registro
+        campo: String,          // Campo acessado
+    },
+    Alloc(Box<Expr>),           // Reserva de memória no heap (tamanho em bytes)
+    Endereco(Box<Expr>),        // Endereço de uma variável, elemento ou campo (&x)
+    Deref(Box<Expr>),           // Valor guardado no endereço (*p)
+    Tupla(Vec<Expr>),           // Valores devolvidos juntos por uma função: (q, r)
+    Desestruturacao {
+        nomes: Vec<String>,     // Variáveis que recebem os valores, na ordem da tupla
+        chamada: Box<Expr>,     // Chamada da função que devolve a tupla
+    },
+    Componente(usize),          // Valor inicial de uma variável preenchida pela desestruturação anterior
+    Condicional {
+        cond: Box<Expr>,        // Condição testada
+        entao: Box<Expr>,       // Valor quando a condição é verdadeira (diferente de zero)
+        senao: Box<Expr>,       // Valor quando a condição é falsa
     },
 }
 
@@ -24,7 +85,52 @@ pub enum Expr {
 pub enum Cmd {
     If { cond: Expr, then_cmds: Vec<Cmd>, else_cmds: Vec<Cmd> }, // Comando condicional
     While { cond: Expr, body: Vec<Cmd> },                         // Comando de repetição
+    Loop { body: Vec<Cmd> },                                      // Repetição encerrada só por break ou return
+    DoWhile { body: Vec<Cmd>, cond: Expr },                       // Repetição com o teste depois do corpo
+    Break,                                                        // Saída do laço mais interno
+    Return(Option<Expr>),                                         // Retorno antecipado (sem valor em procedimentos)
     Atrib { nome: String, expr: Expr },                           // Atribuição de valor
+    AtribIndice { nome: String, indice: Expr, expr: Expr },       // Atribuição a um elemento de vetor
+    AtribCampo { nome: String, campo: String, expr: Expr },       // Atribuição a um campo de registro
+    AtribDeref { ptr: Expr, expr: Expr },                         // Atribuição através de ponteiro (*p = e)
+    AtribComposta { alvo: Expr, operador: String, expr: Expr },   // Atualização no lugar (x += e; x++ soma 1)
+    Print {
+        args: Vec<Expr>,                      // Valores escritos na saída padrão
+        tipos: Vec<Tipo>,                     // Tipo de cada valor, preenchido na anotação de tipos
+        nova_linha: bool,                     // println: termina com uma quebra de linha
+    },
+    Free(Expr),                                                   // Liberação de memória do heap
+    Expr(Expr),                                                   // Chamada feita pelo efeito, com o valor descartado
+    Match {
+        valor: Expr,                          // Valor comparado com os padrões
+        bracos: Vec<(Vec<Expr>, Vec<Cmd>)>,   // Padrões constantes de cada braço e seus comandos
+        padrao: Option<Vec<Cmd>>,             // Comandos do braço `_`, se houver
+    },
+    Assert {
+        cond: Expr,                           // Condição que deve ser verdadeira
+        mensagem: Option<String>,             // Mensagem escrita junto com o erro
+        linha: usize,                         // Linha da condição no arquivo fonte
+        texto: String,                        // Texto da condição como escrito no fonte
+    },
+    Asm {
+        linhas: Vec<String>,                  // Instruções, emitidas como estão
+        operandos: Vec<(String, String)>,     // Operandos `{nome}` e as variáveis ligadas a eles
+    },
+}
+
+// Representa a declaração de um registro (struct)
+#[derive(Debug, PartialEq, Clone)]
+pub struct RegistroDecl {
+    pub nome: String,        // Nome do registro
+    pub campos: Vec<String>, // Campos, na ordem em que são armazenados
+}
+
+// Representa a declaração de uma enumeração: as variantes, na ordem da declaração,
+// valem 0, 1, 2, ... e também são registradas como constantes do programa
+#[derive(Debug, PartialEq, Clone)]
+pub struct EnumDecl {
+    pub nome: String,                 // Nome da enumeração
+    pub variantes: Vec<(String, i64)>, // Variantes e seus valores
 }
 
 // Representa a definição de uma função
@@ -32,30 +138,63 @@ pub enum Cmd {
 pub struct FunDecl {
     pub nome: String,                // Nome da função
     pub parametros: Vec<String>,    // Parâmetros da função
+    pub padroes: HashMap<String, Expr>, // Valores padrão dos parâmetros opcionais
+    pub tipos: HashMap<String, Tipo>, // Tipos anotados dos parâmetros e variáveis locais
+    pub tipo_retorno: Tipo,         // Tipo do valor de retorno
     pub variaveis: Vec<(String, Expr)>, // Variáveis locais e seus valores iniciais
+    pub vetores: Vec<(String, usize)>,  // Vetores locais e seus tamanhos
+    pub aninhadas: Vec<FunDecl>,    // Funções declaradas dentro desta
     pub comandos: Vec<Cmd>,         // Corpo da função
-    pub retorno: Expr,              // Expressão de retorno
+    pub retorno: Option<Expr>,      // Expressão de retorno (procedimentos não têm)
+    pub publica: bool,              // Exportada com `pub fun` para ser chamada de C
+}
+
+// Número máximo de parâmetros de uma função externa (os registradores de argumentos)
+pub const MAX_PARAMETROS_EXTERNA: usize = 6;
+
+// Representa a declaração de uma função externa, escrita em C
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExternDecl {
+    pub nome: String,            // Nome do símbolo chamado
+    pub parametros: Vec<String>, // Parâmetros fixos
+    pub variadica: bool,         // Aceita argumentos além dos fixos (`...`)
 }
 
 // Representa o programa completo
 #[derive(Debug, PartialEq, Clone)]
 pub struct Programa {
+    pub registros: Vec<RegistroDecl>, // Registros declarados
+    pub enums: Vec<EnumDecl>,         // Enumerações declaradas
+    pub constantes: Vec<(String, Expr)>, // Constantes, avaliadas em tempo de compilação
     pub globais: Vec<(String, Expr)>, // Variáveis globais
+    pub tipos: HashMap<String, Tipo>, // Tipos anotados das variáveis globais
+    pub vetores: Vec<(String, usize)>, // Vetores globais e seus tamanhos
     pub funcoes: Vec<FunDecl>,        // Lista de funções definidas
+    pub externas: Vec<ExternDecl>,    // Funções externas declaradas
     pub principal: Vec<Cmd>,          // Comandos principais (main)
     pub retorno: Expr,                // Valor de retorno do main
+    pub tem_principal: bool,          // Indica se o bloco main foi declarado (bibliotecas não têm)
 }
 
+// Argumentos de uma chamada: os passados pela posição e os passados pelo nome
+type Argumentos = (Vec<Expr>, Vec<(String, Expr)>);
+
 // Estrutura do parser: recebe os caracteres da entrada
 pub struct Parser<'a> {
+    entrada: &'a str,            // Código fonte completo, usado para localizar trechos
     tokens: Peekable<Chars<'a>>, // Iterador com capacidade de espiar o próximo caractere
+    registros: HashMap<String, Vec<String>>, // Registros já declarados e seus campos
+    lacos: usize,                // Laços que envolvem o comando atual, para validar o break
 }
 
 impl<'a> Parser<'a> {
     // Cria uma nova instância do parser
     pub fn new(input: &'a str) -> Self {
         Parser {
+            entrada: input,
             tokens: input.chars().peekable(),
+            registros: HashMap::new(),
+            lacos: 0,
         }
     }
 
@@ -84,90 +223,397 @@ impl<'a> Parser<'a> {
         clone.peek().copied()
     }
 
+    // Posição (em bytes) do próximo caractere a ser lido, incluindo brancos
+    fn posicao(&self) -> usize {
+        let restante: usize = self.tokens.clone().map(char::len_utf8).sum();
+        self.entrada.len() - restante
+    }
+
+    // Linha (a partir de 1) de uma posição da entrada
+    fn linha(&self, posicao: usize) -> usize {
+        self.entrada[..posicao].matches('\n').count() + 1
+    }
+
+    // Linha e coluna (a partir de 1) do caractere em uma posição da entrada
+    fn local(&self, posicao: usize) -> (usize, usize) {
+        let inicio_linha = self.entrada[..posicao].rfind('\n').map_or(0, |i| i + 1);
+        (self.linha(posicao), self.entrada[inicio_linha..posicao].chars().count() + 1)
+    }
+
+    // Posição do próximo caractere que não é espaço
+    fn posicao_token(&self) -> usize {
+        let resto = &self.entrada[self.posicao()..];
+        self.posicao() + resto.len() - resto.trim_start().len()
+    }
+
     // Inicia o parsing de um programa completo
     pub fn parse_programa(&mut self) -> Result<Programa, String> {
+        let mut registros = Vec::new();
+        let mut enums: Vec<EnumDecl> = Vec::new();
+        let mut constantes = Vec::new();
         let mut globais = Vec::new();
+        let mut tipos = HashMap::new();
+        let mut vetores = Vec::new();
         let mut funcoes = Vec::new();
+        let mut externas = Vec::new();
 
-        // Processa as variáveis globais e funções até encontrar o main
+        // Processa os registros, as constantes, as variáveis globais e funções até encontrar
+        // o main; bibliotecas não têm main e terminam no fim da entrada
+        let mut tem_principal = true;
         loop {
             if self.parse_kw("main")? {
                 break;
+            } else if self.peek().is_none() {
+                tem_principal = false;
+                break;
             } else if self.parse_kw("var")? {
+                if self.peek() == Some('(') {
+                    return Err("Desestruturação só é permitida em variáveis locais".into());
+                }
+                let nome = self.parse_var()?;
+                if self.peek() == Some('[') {
+                    vetores.push((nome, self.parse_tamanho()?));
+                    continue;
+                }
+                let expr = self.parse_decl_var(&nome, &mut tipos)?;
+                globais.push((nome, expr));
+            } else if self.parse_kw("const")? {
                 let nome = self.parse_var()?;
                 self.expect('=')?;
                 let expr = self.parse_expr()?;
                 self.expect(';')?;
-                globais.push((nome, expr));
+                constantes.push((nome, expr));
+            } else if self.parse_kw("struct")? {
+                registros.push(self.parse_registro()?);
+            } else if self.parse_kw("enum")? {
+                let decl = self.parse_enum()?;
+                if enums.iter().any(|e| e.nome == decl.nome) {
+                    return Err(format!("Enum '{}' declarado mais de uma vez", decl.nome));
+                }
+                constantes.extend(decl.variantes.iter().map(|(nome, valor)| (nome.clone(), Expr::Const(*valor))));
+                enums.push(decl);
             } else if self.parse_kw("fun")? {
                 funcoes.push(self.parse_fundecl()?);
+            } else if self.parse_kw("pub")? {
+                self.expect_kw("fun")?;
+                let mut func = self.parse_fundecl()?;
+                func.publica = true;
+                funcoes.push(func);
+            } else if self.parse_kw("extern")? {
+                self.expect_kw("fun")?;
+                externas.push(self.parse_extern()?);
             } else {
-                return Err("Esperado 'fun', 'var' ou 'main'".into());
+                return Err("Esperado 'struct', 'enum', 'const', 'fun', 'pub', 'extern', 'var' ou 'main'".into());
             }
         }
 
         // Processa o bloco principal do programa
-        self.expect('{')?;
         let mut cmds = Vec::new();
-        while !self.parse_kw("return")? {
-            cmds.push(self.parse_cmd()?);
+        let mut retorno = Expr::Const(0);
+        if tem_principal {
+            self.expect('{')?;
+            while !self.parse_kw("return")? {
+                cmds.push(self.parse_cmd()?);
+            }
+            retorno = self.parse_expr()?;
+            self.expect(';')?;
+            self.expect('}')?;
         }
-        let retorno = self.parse_expr()?;
-        self.expect(';')?;
-        self.expect('}')?;
 
         Ok(Programa {
+            registros,
+            enums,
+            constantes,
             globais,
+            tipos,
+            vetores,
             funcoes,
+            externas,
             principal: cmds,
             retorno,
+            tem_principal,
         })
     }
 
+    // Faz o parsing de uma declaração de registro: `Point { x, y }`
+    fn parse_registro(&mut self) -> Result<RegistroDecl, String> {
+        let nome = self.parse_var()?;
+        if self.registros.contains_key(&nome) {
+            return Err(format!("Registro '{}' declarado mais de uma vez", nome));
+        }
+        self.expect('{')?;
+        let mut campos: Vec<String> = Vec::new();
+        while self.peek() != Some('}') {
+            if !campos.is_empty() {
+                self.expect(',')?;
+            }
+            let campo = self.parse_var()?;
+            if campos.contains(&campo) {
+                return Err(format!("Campo '{}' repetido no registro '{}'", campo, nome));
+            }
+            campos.push(campo);
+        }
+        self.expect('}')?;
+        if campos.is_empty() {
+            return Err(format!("Registro '{}' sem campos", nome));
+        }
+        self.registros.insert(nome.clone(), campos.clone());
+        Ok(RegistroDecl { nome, campos })
+    }
+
+    // Faz o parsing de uma declaração de enumeração: `Color { Red, Green, Blue }`
+    fn parse_enum(&mut self) -> Result<EnumDecl, String> {
+        let nome = self.parse_var()?;
+        self.expect('{')?;
+        let mut variantes: Vec<(String, i64)> = Vec::new();
+        while self.peek() != Some('}') {
+            if !variantes.is_empty() {
+                self.expect(',')?;
+            }
+            let variante = self.parse_var()?;
+            if variantes.iter().any(|(v, _)| *v == variante) {
+                return Err(format!("Variante '{}' repetida no enum '{}'", variante, nome));
+            }
+            variantes.push((variante, variantes.len() as i64));
+        }
+        self.expect('}')?;
+        if variantes.is_empty() {
+            return Err(format!("Enum '{}' sem variantes", nome));
+        }
+        Ok(EnumDecl { nome, variantes })
+    }
+
+    // Faz o parsing do valor inicial de uma variável: uma expressão ou a
+    // construção de um registro já declarado, como `Point { x: 1, y: 2 }`
+    fn parse_init(&mut self) -> Result<Expr, String> {
+        let inicio = self.tokens.clone();
+        if let Some(c) = self.peek() {
+            if c.is_ascii_alphabetic() {
+                let tipo = self.parse_var()?;
+                if self.registros.contains_key(&tipo) && self.peek() == Some('{') {
+                    return self.parse_construcao(tipo);
+                }
+            }
+        }
+        self.tokens = inicio;
+        self.parse_expr()
+    }
+
+    // Faz o parsing do restante da declaração de uma variável escalar ou registro,
+    // depois do nome: `: u64 = expr;`. O tipo anotado é guardado em `tipos`.
+    fn parse_decl_var(&mut self, nome: &str, tipos: &mut HashMap<String, Tipo>) -> Result<Expr, String> {
+        let tipo = self.parse_tipo()?;
+        self.expect('=')?;
+        let expr = self.parse_init()?;
+        self.expect(';')?;
+        if let Some(tipo) = tipo {
+            if let Expr::Registro { .. } = expr {
+                return Err(format!("Registro '{}' não pode ter tipo inteiro", nome));
+            }
+            tipos.insert(nome.to_string(), tipo);
+        }
+        Ok(expr)
+    }
+
+    // Faz o parsing de uma desestruturação depois de `var`: `(a, b: u64) = f(x);`.
+    // A primeira variável recebe a chamada e as demais são preenchidas por ela.
+    fn parse_desestruturacao(&mut self, tipos: &mut HashMap<String, Tipo>) -> Result<Vec<(String, Expr)>, String> {
+        self.expect('(')?;
+        let mut nomes = Vec::new();
+        loop {
+            let nome = self.parse_var()?;
+            if let Some(tipo) = self.parse_tipo()? {
+                tipos.insert(nome.clone(), tipo);
+            }
+            nomes.push(nome);
+            if self.peek() != Some(',') {
+                break;
+            }
+            self.next();
+        }
+        self.expect(')')?;
+        if nomes.len() < 2 {
+            return Err("Desestruturação precisa de pelo menos dois nomes".into());
+        }
+        self.expect('=')?;
+        let chamada = self.parse_expr()?;
+        self.expect(';')?;
+        let mut variaveis = vec![(nomes[0].clone(), Expr::Desestruturacao { nomes: nomes.clone(), chamada: Box::new(chamada) })];
+        variaveis.extend(nomes.into_iter().enumerate().skip(1).map(|(i, nome)| (nome, Expr::Componente(i))));
+        Ok(variaveis)
+    }
+
+    // Faz o parsing de uma anotação de tipo opcional: `: u64`
+    fn parse_tipo(&mut self) -> Result<Option<Tipo>, String> {
+        if self.peek() != Some(':') {
+            return Ok(None);
+        }
+        self.next();
+        match self.parse_var()?.as_str() {
+            "i64" => Ok(Some(Tipo::I64)),
+            "u64" => Ok(Some(Tipo::U64)),
+            outro => Err(format!("Tipo desconhecido: '{}'", outro)),
+        }
+    }
+
+    // Faz o parsing dos campos informados na construção de um registro
+    fn parse_construcao(&mut self, tipo: String) -> Result<Expr, String> {
+        self.expect('{')?;
+        let mut campos: Vec<(String, Expr)> = Vec::new();
+        while self.peek() != Some('}') {
+            if !campos.is_empty() {
+                self.expect(',')?;
+            }
+            let campo = self.parse_var()?;
+            if !self.registros[&tipo].contains(&campo) {
+                return Err(format!("Registro '{}' não tem o campo '{}'", tipo, campo));
+            }
+            if campos.iter().any(|(c, _)| *c == campo) {
+                return Err(format!("Campo '{}' informado mais de uma vez", campo));
+            }
+            self.expect(':')?;
+            campos.push((campo, self.parse_expr()?));
+        }
+        self.expect('}')?;
+        Ok(Expr::Registro { tipo, campos })
+    }
+
+    // Faz o parsing da declaração de uma função externa: `printf(fmt, ...);`
+    fn parse_extern(&mut self) -> Result<ExternDecl, String> {
+        let nome = self.parse_var()?;
+        self.expect('(')?;
+        let mut parametros = Vec::new();
+        let mut variadica = false;
+        while self.peek() != Some(')') {
+            if !parametros.is_empty() {
+                self.expect(',')?;
+            }
+            if self.parse_op("...") {
+                variadica = true;
+                break;
+            }
+            parametros.push(self.parse_var()?);
+        }
+        self.expect(')')?;
+        self.expect(';')?;
+        if parametros.len() > MAX_PARAMETROS_EXTERNA {
+            return Err(format!(
+                "Função externa '{}' com mais de {} parâmetros",
+                nome, MAX_PARAMETROS_EXTERNA
+            ));
+        }
+        Ok(ExternDecl { nome, parametros, variadica })
+    }
+
     // Faz o parsing de uma função
     fn parse_fundecl(&mut self) -> Result<FunDecl, String> {
         let nome = self.parse_var()?;
         self.expect('(')?;
         let mut parametros = Vec::new();
+        let mut tipos = HashMap::new();
+        let mut padroes = HashMap::new();
         if self.peek() != Some(')') {
-            parametros.push(self.parse_var()?);
-            while self.peek() == Some(',') {
+            loop {
+                let param = self.parse_var()?;
+                if let Some(tipo) = self.parse_tipo()? {
+                    tipos.insert(param.clone(), tipo);
+                }
+                // Parâmetros opcionais (`passo = 1`) ficam depois dos obrigatórios
+                if self.peek() == Some('=') {
+                    self.next();
+                    padroes.insert(param.clone(), self.parse_expr()?);
+                } else if !padroes.is_empty() {
+                    return Err(format!("Parâmetro '{}' sem valor padrão depois de parâmetros opcionais", param));
+                }
+                parametros.push(param);
+                if self.peek() != Some(',') {
+                    break;
+                }
                 self.next();
-                parametros.push(self.parse_var()?);
             }
         }
         self.expect(')')?;
+        let tipo_retorno = self.parse_tipo()?.unwrap_or_default();
         self.expect('{')?;
 
+        // Declarações de variáveis, vetores e funções aninhadas, em qualquer ordem
         let mut variaveis = Vec::new();
-        while self.parse_kw("var")? {
+        let mut vetores = Vec::new();
+        let mut aninhadas = Vec::new();
+        loop {
+            if self.parse_kw("fun")? {
+                aninhadas.push(self.parse_fundecl()?);
+                continue;
+            }
+            if !self.parse_kw("var")? {
+                break;
+            }
+            if self.peek() == Some('(') {
+                variaveis.extend(self.parse_desestruturacao(&mut tipos)?);
+                continue;
+            }
             let nome = self.parse_var()?;
-            self.expect('=')?;
-            let expr = self.parse_expr()?;
-            self.expect(';')?;
+            if self.peek() == Some('[') {
+                vetores.push((nome, self.parse_tamanho()?));
+                continue;
+            }
+            let expr = self.parse_decl_var(&nome, &mut tipos)?;
             variaveis.push((nome, expr));
         }
 
+        // O corpo termina com `return e;`; procedimentos terminam sem ele ou com `return;`
         let mut comandos = Vec::new();
-        while !self.parse_kw("return")? {
+        let mut retorno = None;
+        while self.peek() != Some('}') {
+            if self.parse_kw("return")? {
+                // `return;` fica entre os comandos, para ser conferido com os demais retornos
+                if self.peek() == Some(';') {
+                    self.next();
+                    comandos.push(Cmd::Return(None));
+                } else {
+                    retorno = Some(self.parse_expr()?);
+                    self.expect(';')?;
+                }
+                break;
+            }
             comandos.push(self.parse_cmd()?);
         }
-        let retorno = self.parse_expr()?;
-        self.expect(';')?;
         self.expect('}')?;
 
         Ok(FunDecl {
             nome,
             parametros,
+            padroes,
+            tipos,
+            tipo_retorno,
             variaveis,
+            vetores,
+            aninhadas,
             comandos,
             retorno,
+            publica: false,
         })
     }
 
-    // Faz o parsing de um comando (if, while ou atribuição)
+    // Faz o parsing de um comando (if, while, print, free ou atribuição)
     fn parse_cmd(&mut self) -> Result<Cmd, String> {
-        if self.parse_kw("if")? {
+        if self.parse_kw("free")? {
+            self.expect('(')?;
+            let ptr = self.parse_expr()?;
+            self.expect(')')?;
+            self.expect(';')?;
+            Ok(Cmd::Free(ptr))
+        } else if self.parse_kw("match")? {
+            self.parse_match()
+        } else if self.parse_kw("assert")? {
+            self.parse_assert()
+        } else if self.parse_kw("asm")? {
+            self.parse_asm()
+        } else if self.parse_kw("print")? {
+            self.parse_print(false)
+        } else if self.parse_kw("println")? {
+            self.parse_print(true)
+        } else if self.parse_kw("if")? {
             let cond = self.parse_expr()?;
             self.expect('{')?;
             let mut then_cmds = Vec::new();
@@ -185,25 +631,216 @@ impl<'a> Parser<'a> {
             Ok(Cmd::If { cond, then_cmds, else_cmds })
         } else if self.parse_kw("while")? {
             let cond = self.parse_expr()?;
-            self.expect('{')?;
-            let mut body = Vec::new();
-            while self.peek() != Some('}') {
-                body.push(self.parse_cmd()?);
-            }
-            self.expect('}')?;
+            let body = self.parse_corpo_laco()?;
             Ok(Cmd::While { cond, body })
+        } else if self.parse_kw("loop")? {
+            let body = self.parse_corpo_laco()?;
+            Ok(Cmd::Loop { body })
+        } else if self.parse_kw("do")? {
+            let body = self.parse_corpo_laco()?;
+            self.expect_kw("while")?;
+            let cond = self.parse_expr()?;
+            self.expect(';')?;
+            Ok(Cmd::DoWhile { body, cond })
+        } else if self.parse_kw("break")? {
+            if self.lacos == 0 {
+                return Err("'break' fora de um laço".into());
+            }
+            self.expect(';')?;
+            Ok(Cmd::Break)
+        } else if self.parse_kw("return")? {
+            // O return que encerra o corpo é lido por quem chama; este está dentro de um bloco
+            let expr = if self.peek() == Some(';') { None } else { Some(self.parse_expr()?) };
+            self.expect(';')?;
+            Ok(Cmd::Return(expr))
+        } else if self.peek() == Some('*') {
+            self.next();
+            let ptr = self.parse_prim()?;
+            let alvo = Expr::Deref(Box::new(ptr.clone()));
+            self.parse_atribuicao(alvo, |expr| Cmd::AtribDeref { ptr, expr })
         } else {
+            let inicio = self.tokens.clone();
             let nome = self.parse_var()?;
-            self.expect('=')?;
+            // Chamada usada como comando: `f(x);`
+            if self.peek() == Some('(') {
+                self.tokens = inicio;
+                let chamada = self.parse_prim()?;
+                self.expect(';')?;
+                return Ok(Cmd::Expr(chamada));
+            }
+            if self.peek() == Some('[') {
+                self.next();
+                let indice = self.parse_expr()?;
+                self.expect(']')?;
+                let alvo = Expr::Indice { nome: nome.clone(), indice: Box::new(indice.clone()) };
+                return self.parse_atribuicao(alvo, |expr| Cmd::AtribIndice { nome, indice, expr });
+            }
+            if self.peek() == Some('.') {
+                self.next();
+                let campo = self.parse_var()?;
+                let alvo = Expr::Campo { nome: nome.clone(), campo: campo.clone() };
+                return self.parse_atribuicao(alvo, |expr| Cmd::AtribCampo { nome, campo, expr });
+            }
+            self.parse_atribuicao(Expr::Var(nome.clone()), |expr| Cmd::Atrib { nome, expr })
+        }
+    }
+
+    // Faz o parsing do que vem depois do alvo de uma atribuição: `= e;` monta o
+    // comando com `atrib`; `+= e;`, `-= e;`, `*= e;`, `/= e;`, `%= e;`, `++;` e `--;`
+    // atualizam o alvo no lugar
+    fn parse_atribuicao(&mut self, alvo: Expr, atrib: impl FnOnce(Expr) -> Cmd) -> Result<Cmd, String> {
+        let compostos = [("+=", "+"), ("-=", "-"), ("*=", "*"), ("/=", "/"), ("%=", "%")];
+        let incrementos = [("++", "+"), ("--", "-")];
+        let cmd = if let Some((_, operador)) = compostos.iter().find(|(op, _)| self.parse_op(op)) {
             let expr = self.parse_expr()?;
-            self.expect(';')?;
-            Ok(Cmd::Atrib { nome, expr })
+            Cmd::AtribComposta { alvo, operador: operador.to_string(), expr }
+        } else if let Some((_, operador)) = incrementos.iter().find(|(op, _)| self.parse_op(op)) {
+            Cmd::AtribComposta { alvo, operador: operador.to_string(), expr: Expr::Const(1) }
+        } else {
+            self.expect('=')?;
+            atrib(self.parse_expr()?)
+        };
+        self.expect(';')?;
+        Ok(cmd)
+    }
+
+    // Faz o parsing dos argumentos de print/println: `(e1, e2, ...);`
+    fn parse_print(&mut self, nova_linha: bool) -> Result<Cmd, String> {
+        self.expect('(')?;
+        let mut args = Vec::new();
+        if self.peek() != Some(')') {
+            args.push(self.parse_expr()?);
+            while self.peek() == Some(',') {
+                self.next();
+                args.push(self.parse_expr()?);
+            }
+        }
+        self.expect(')')?;
+        self.expect(';')?;
+        // Os tipos dos valores são calculados depois, na anotação de tipos
+        let tipos = vec![Tipo::I64; args.len()];
+        Ok(Cmd::Print { args, tipos, nova_linha })
+    }
+
+    // Faz o parsing de um assert, guardando a linha e o texto da condição:
+    // `(cond);` ou `(cond, "mensagem");`
+    fn parse_assert(&mut self) -> Result<Cmd, String> {
+        self.expect('(')?;
+        let inicio = self.posicao();
+        let cond = self.parse_expr()?;
+        let trecho = &self.entrada[inicio..self.posicao()];
+        let linha = self.linha(inicio + trecho.len() - trecho.trim_start().len());
+        let texto = trecho.split_whitespace().collect::<Vec<_>>().join(" ");
+        let mut mensagem = None;
+        if self.peek() == Some(',') {
+            self.next();
+            mensagem = Some(self.parse_string()?);
+        }
+        self.expect(')')?;
+        self.expect(';')?;
+        Ok(Cmd::Assert { cond, mensagem, linha, texto })
+    }
+
+    // Faz o parsing de um comando match, depois da palavra-chave:
+    // `x { 1 => { ... } 2 | 3 => { ... } _ => { ... } }`
+    fn parse_match(&mut self) -> Result<Cmd, String> {
+        let valor = self.parse_expr()?;
+        self.expect('{')?;
+        let mut bracos = Vec::new();
+        let mut padrao = None;
+        while self.peek() != Some('}') {
+            if padrao.is_some() {
+                return Err("O braço '_' deve ser o último do match".into());
+            }
+            if self.peek() == Some('_') {
+                self.next();
+                self.expect_op("=>")?;
+                padrao = Some(self.parse_bloco()?);
+                continue;
+            }
+            // Os padrões não usam operadores relacionais, para não confundir com "=>"
+            let mut padroes = vec![self.parse_exp_d()?];
+            while self.peek() == Some('|') {
+                self.next();
+                padroes.push(self.parse_exp_d()?);
+            }
+            self.expect_op("=>")?;
+            bracos.push((padroes, self.parse_bloco()?));
+        }
+        self.expect('}')?;
+        Ok(Cmd::Match { valor, bracos, padrao })
+    }
+
+    // Faz o parsing de um bloco de assembly: as instruções, uma por string, seguidas
+    // das ligações dos operandos, como em `asm { "mov rdi, {c}", "syscall", c = x }`
+    fn parse_asm(&mut self) -> Result<Cmd, String> {
+        self.expect('{')?;
+        let mut linhas = Vec::new();
+        let mut operandos: Vec<(String, String)> = Vec::new();
+        while self.peek() != Some('}') {
+            if self.peek() == Some('"') {
+                if !operandos.is_empty() {
+                    return Err("As instruções do asm devem vir antes dos operandos".into());
+                }
+                linhas.push(self.parse_string()?);
+            } else {
+                let operando = self.parse_var()?;
+                if operandos.iter().any(|(o, _)| *o == operando) {
+                    return Err(format!("Operando '{}' ligado mais de uma vez no asm", operando));
+                }
+                self.expect('=')?;
+                operandos.push((operando, self.parse_var()?));
+            }
+            if self.peek() != Some('}') {
+                self.expect(',')?;
+            }
         }
+        self.expect('}')?;
+
+        // Só os `{nome}` ligados são operandos; os demais, como as máscaras `{k1}` e `{z}`
+        // do AVX-512, ficam nas instruções. Cada ligação precisa aparecer em alguma delas.
+        for (operando, _) in &operandos {
+            if !linhas.iter().any(|l| l.contains(&format!("{{{}}}", operando))) {
+                return Err(format!("Operando '{}' não é usado no asm", operando));
+            }
+        }
+        Ok(Cmd::Asm { linhas, operandos })
+    }
+
+    // Faz o parsing de um bloco de comandos entre chaves
+    fn parse_bloco(&mut self) -> Result<Vec<Cmd>, String> {
+        self.expect('{')?;
+        let mut cmds = Vec::new();
+        while self.peek() != Some('}') {
+            cmds.push(self.parse_cmd()?);
+        }
+        self.expect('}')?;
+        Ok(cmds)
+    }
+
+    // Faz o parsing do bloco de um laço, onde o break é permitido
+    fn parse_corpo_laco(&mut self) -> Result<Vec<Cmd>, String> {
+        self.lacos += 1;
+        let body = self.parse_bloco();
+        self.lacos -= 1;
+        body
+    }
+
+    // Faz o parsing do tamanho na declaração de um vetor: `[10];`
+    fn parse_tamanho(&mut self) -> Result<usize, String> {
+        self.expect('[')?;
+        let tamanho = match self.parse_const()? {
+            Expr::Const(n) if n > 0 => n as usize,
+            _ => return Err("Tamanho de vetor deve ser positivo".into()),
+        };
+        self.expect(']')?;
+        self.expect(';')?;
+        Ok(tamanho)
     }
 
     // Parsing de uma expressão, incluindo operadores relacionais
     fn parse_expr(&mut self) -> Result<Expr, String> {
-        let mut expr = self.parse_exp_a()?;
+        let mut expr = self.parse_exp_d()?;
 
         while let Some(op) = self.peek() {
             let operador = match op {
@@ -216,7 +853,7 @@ impl<'a> Parser<'a> {
                 '<' | '>' => self.next().unwrap().to_string(),
                 _ => break,
             };
-            let dir = self.parse_exp_a()?;
+            let dir = self.parse_exp_d()?;
             expr = Expr::OpBin {
                 operador,
                 esq: Box::new(expr),
@@ -226,6 +863,27 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
+    // Parsing de deslocamentos de bits (<< e >>)
+    fn parse_exp_d(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_exp_a()?;
+        loop {
+            let operador = if self.parse_op("<<") {
+                "<<"
+            } else if self.parse_op(">>") {
+                ">>"
+            } else {
+                break;
+            };
+            let dir = self.parse_exp_a()?;
+            expr = Expr::OpBin {
+                operador: operador.into(),
+                esq: Box::new(expr),
+                dir: Box::new(dir),
+            };
+        }
+        Ok(expr)
+    }
+
     // Parsing de expressões aditivas (soma/subtração)
     fn parse_exp_a(&mut self) -> Result<Expr, String> {
         let mut expr = self.parse_exp_m()?;
@@ -245,11 +903,11 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
-    // Parsing de expressões multiplicativas (multiplicação/divisão)
+    // Parsing de expressões multiplicativas (multiplicação/divisão/resto)
     fn parse_exp_m(&mut self) -> Result<Expr, String> {
         let mut expr = self.parse_prim()?;
         while let Some(op) = self.peek() {
-            if op == '*' || op == '/' {
+            if op == '*' || op == '/' || op == '%' {
                 let op = self.next().unwrap().to_string();
                 let dir = self.parse_prim()?;
                 expr = Expr::OpBin {
@@ -266,49 +924,192 @@ impl<'a> Parser<'a> {
 
     // Parsing de expressões primárias (número, variável, chamada, parênteses)
     fn parse_prim(&mut self) -> Result<Expr, String> {
+        if self.parse_kw("if")? {
+            return self.parse_condicional();
+        }
         match self.peek() {
             Some(c) if c.is_ascii_digit() => self.parse_const(),
             Some(c) if c.is_ascii_alphabetic() => {
+                let inicio = self.posicao_token();
                 let nome = self.parse_var()?;
+                if nome == "read" {
+                    self.expect('(')?;
+                    self.expect(')')?;
+                    return Ok(Expr::Read);
+                }
+                if nome == "alloc" {
+                    self.expect('(')?;
+                    let tamanho = self.parse_expr()?;
+                    self.expect(')')?;
+                    return Ok(Expr::Alloc(Box::new(tamanho)));
+                }
                 if self.peek() == Some('(') {
                     self.next();
-                    let mut args = Vec::new();
-                    if self.peek() != Some(')') {
-                        args.push(self.parse_expr()?);
-                        while self.peek() == Some(',') {
-                            self.next();
-                            args.push(self.parse_expr()?);
-                        }
-                    }
-                    self.expect(')')?;
-                    Ok(Expr::Call { nome, args })
+                    let (args, nomeados) = self.parse_argumentos()?;
+                    let span = Span { inicio: self.local(inicio), fim: self.local(self.posicao() - 1) };
+                    Ok(Expr::Call { nome, args, nomeados, span })
+                } else if self.peek() == Some('[') {
+                    self.next();
+                    let indice = self.parse_expr()?;
+                    self.expect(']')?;
+                    Ok(Expr::Indice { nome, indice: Box::new(indice) })
+                } else if self.peek() == Some('.') {
+                    self.next();
+                    let campo = self.parse_var()?;
+                    Ok(Expr::Campo { nome, campo })
                 } else {
                     Ok(Expr::Var(nome))
                 }
             }
+            Some('"') => Ok(Expr::Str(self.parse_string()?)),
+            Some('&') => {
+                self.next();
+                match self.parse_prim()? {
+                    e @ (Expr::Var(_) | Expr::Indice { .. } | Expr::Campo { .. } | Expr::Deref(_)) => {
+                        Ok(Expr::Endereco(Box::new(e)))
+                    }
+                    _ => Err("'&' só pode ser aplicado a variáveis, elementos ou campos".into()),
+                }
+            }
+            Some('*') => {
+                self.next();
+                Ok(Expr::Deref(Box::new(self.parse_prim()?)))
+            }
+            // Expressão entre parênteses ou tupla, quando há vírgulas
             Some('(') => {
                 self.next();
                 let e = self.parse_expr()?;
+                if self.peek() != Some(',') {
+                    self.expect(')')?;
+                    return Ok(e);
+                }
+                let mut valores = vec![e];
+                while self.peek() == Some(',') {
+                    self.next();
+                    valores.push(self.parse_expr()?);
+                }
                 self.expect(')')?;
-                Ok(e)
+                Ok(Expr::Tupla(valores))
             }
             Some(c) => Err(format!("Token inesperado: '{}'", c)),
             None => Err("Fim inesperado".into()),
         }
     }
 
-    // Parsing de constantes inteiras
+    // Faz o parsing dos argumentos de uma chamada, depois do '(': os passados pela
+    // posição e, depois deles, os passados pelo nome do parâmetro (`passo = 2`)
+    fn parse_argumentos(&mut self) -> Result<Argumentos, String> {
+        let mut args = Vec::new();
+        let mut nomeados = Vec::new();
+        while self.peek() != Some(')') {
+            if !args.is_empty() || !nomeados.is_empty() {
+                self.expect(',')?;
+            }
+            let inicio = self.tokens.clone();
+            if self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                let nome = self.parse_var()?;
+                if self.peek() == Some('=') && !self.parse_op("==") {
+                    self.next();
+                    nomeados.push((nome, self.parse_expr()?));
+                    continue;
+                }
+                self.tokens = inicio;
+            }
+            if !nomeados.is_empty() {
+                return Err("Argumento passado pela posição depois de argumentos nomeados".into());
+            }
+            args.push(self.parse_expr()?);
+        }
+        self.expect(')')?;
+        Ok((args, nomeados))
+    }
+
+    // Faz o parsing de um if usado como expressão, depois da palavra-chave:
+    // `c { a } else { b }`, com `else if` para encadear condições
+    fn parse_condicional(&mut self) -> Result<Expr, String> {
+        let cond = self.parse_expr()?;
+        self.expect('{')?;
+        let entao = self.parse_expr()?;
+        self.expect('}')?;
+        self.expect_kw("else")?;
+        let senao = if self.parse_kw("if")? {
+            self.parse_condicional()?
+        } else {
+            self.expect('{')?;
+            let senao = self.parse_expr()?;
+            self.expect('}')?;
+            senao
+        };
+        Ok(Expr::Condicional { cond: Box::new(cond), entao: Box::new(entao), senao: Box::new(senao) })
+    }
+
+    // Parsing de constantes inteiras: decimais ou com prefixo 0x, 0b ou 0o,
+    // com `_` opcional entre os dígitos (1_000_000)
     fn parse_const(&mut self) -> Result<Expr, String> {
-        let mut valor = 0;
-        while let Some(c) = self.peek() {
-            if c.is_ascii_digit() {
-                valor = valor * 10 + c.to_digit(10).unwrap() as i32;
-                self.next();
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() => {}
+            _ => return Err("Esperado número".into()),
+        }
+        // Depois do primeiro caractere, o literal é lido diretamente: espaços o encerram
+        let mut texto = self.next().unwrap().to_string();
+        while let Some(&c) = self.tokens.peek() {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                texto.push(c);
+                self.tokens.next();
             } else {
                 break;
             }
         }
-        Ok(Expr::Const(valor))
+
+        let (base, digitos) = match texto.get(..2) {
+            Some("0x") => (16, &texto[2..]),
+            Some("0b") => (2, &texto[2..]),
+            Some("0o") => (8, &texto[2..]),
+            _ => (10, texto.as_str()),
+        };
+        let invalido = digitos.is_empty()
+            || digitos.starts_with('_')
+            || digitos.ends_with('_')
+            || digitos.contains("__")
+            || digitos.chars().any(|c| c != '_' && !c.is_digit(base));
+        if invalido {
+            return Err(format!("Literal inteiro inválido: '{}'", texto));
+        }
+        // Literais hexadecimais, binários e octais podem ocupar os 64 bits e, acima de
+        // i64::MAX, têm o tipo u64; a constante guarda o inteiro com sinal que tem essa
+        // representação (0xFFFF_FFFF_FFFF_FFFF é -1). Decimais precisam caber em um i64,
+        // para não mudarem de valor em silêncio quando usados como i64.
+        let valor = u64::from_str_radix(&digitos.replace('_', ""), base)
+            .map_err(|_| format!("Literal '{}' fora do intervalo dos inteiros de 64 bits", texto))?;
+        if base == 10 && valor > i64::MAX as u64 {
+            return Err(format!(
+                "Literal decimal '{}' fora do intervalo de i64; escreva valores u64 maiores em hexadecimal",
+                texto
+            ));
+        }
+        Ok(Expr::Const(valor as i64))
+    }
+
+    // Parsing de literais de string entre aspas duplas, com escapes \n, \t, \\ e \"
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut texto = String::new();
+        loop {
+            // Os caracteres são lidos diretamente: espaços fazem parte da string
+            match self.tokens.next() {
+                Some('"') => return Ok(texto),
+                Some('\\') => match self.tokens.next() {
+                    Some('n') => texto.push('\n'),
+                    Some('t') => texto.push('\t'),
+                    Some('\\') => texto.push('\\'),
+                    Some('"') => texto.push('"'),
+                    Some(c) => return Err(format!("Escape inválido: '\\{}'", c)),
+                    None => return Err("String não terminada".into()),
+                },
+                Some(c) => texto.push(c),
+                None => return Err("String não terminada".into()),
+            }
+        }
     }
 
     // Parsing de identificadores (nomes de variáveis ou funções)
@@ -351,6 +1152,29 @@ impl<'a> Parser<'a> {
         Ok(true)
     }
 
+    // Tenta fazer o parsing de um operador de mais de um caractere, como "<<"
+    fn parse_op(&mut self, op: &str) -> bool {
+        let mut clone = self.tokens.clone();
+        while let Some(&c) = clone.peek() {
+            if c.is_whitespace() { clone.next(); } else { break; }
+        }
+        if op.chars().all(|ch| Some(ch) == clone.next()) {
+            self.tokens = clone;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Espera obrigatoriamente por um operador de mais de um caractere
+    fn expect_op(&mut self, op: &str) -> Result<(), String> {
+        if self.parse_op(op) {
+            Ok(())
+        } else {
+            Err(format!("Esperado '{}'", op))
+        }
+    }
+
     // Espera obrigatoriamente que uma palavra-chave apareça
     fn expect_kw(&mut self, kw: &str) -> Result<(), String> {
         if self.parse_kw(kw)? {
@@ -386,6 +1210,40 @@ mod tests {
         assert_eq!(expr, Expr::Const(123));
     }
 
+    // Testa literais hexadecimais, binários, octais e com separadores
+    #[test]
+    fn test_parse_const_bases() {
+        let valor = |code: &str| Parser::new(code).parse_expr();
+        assert_eq!(valor("0x1F"), Ok(Expr::Const(31)));
+        assert_eq!(valor("0b1010"), Ok(Expr::Const(10)));
+        assert_eq!(valor("0o17"), Ok(Expr::Const(15)));
+        assert_eq!(valor("1_000_000"), Ok(Expr::Const(1_000_000)));
+        assert_eq!(valor("0x7fff_ffff"), Ok(Expr::Const(i32::MAX as i64)));
+    }
+
+    // Testa literais malformados ou fora do intervalo
+    #[test]
+    fn test_parse_const_invalida() {
+        let erro = |code: &str| Parser::new(code).parse_expr().unwrap_err();
+        assert_eq!(erro("0b102"), "Literal inteiro inválido: '0b102'");
+        assert_eq!(erro("0x"), "Literal inteiro inválido: '0x'");
+        assert_eq!(erro("1__0"), "Literal inteiro inválido: '1__0'");
+        assert_eq!(erro("10_"), "Literal inteiro inválido: '10_'");
+        assert!(erro("9223372036854775808").contains("fora do intervalo de i64"));
+        assert!(erro("18446744073709551616").contains("fora do intervalo"));
+        assert!(erro("0x1_0000_0000_0000_0000").contains("fora do intervalo"));
+    }
+
+    // Testa literais que só cabem em 64 bits
+    #[test]
+    fn test_parse_const_64_bits() {
+        let valor = |code: &str| Parser::new(code).parse_expr();
+        assert_eq!(valor("4294967296"), Ok(Expr::Const(1 << 32)));
+        assert_eq!(valor("9223372036854775807"), Ok(Expr::Const(i64::MAX)));
+        assert_eq!(valor("0xFFFF_FFFF_FFFF_FFFF"), Ok(Expr::Const(-1)));
+        assert_eq!(valor("0x8000_0000_0000_0000"), Ok(Expr::Const(i64::MIN)));
+    }
+
     // Testa o parsing de uma variável simples
     #[test]
     fn test_parse_var() {
@@ -422,6 +1280,8 @@ mod tests {
             Expr::Call {
                 nome: "soma".into(),
                 args: vec![Expr::Const(1), Expr::Var("x".into())],
+                nomeados: vec![],
+                span: Span { inicio: (1, 1), fim: (1, 10) },
             }
         );
     }
@@ -473,4 +1333,372 @@ mod tests {
             _ => panic!("Esperado Cmd::While"),
         }
     }
+
+    // Testa o parsing de loop, do-while, break e return dentro de blocos
+    #[test]
+    fn test_parse_lacos() {
+        let mut parser = Parser::new("loop { if x { break; } else { return 2; } }");
+        let cmd = parser.parse_cmd().unwrap();
+        let condicional = Cmd::If { cond: Expr::Var("x".into()), then_cmds: vec![Cmd::Break], else_cmds: vec![Cmd::Return(Some(Expr::Const(2)))] };
+        assert_eq!(cmd, Cmd::Loop { body: vec![condicional] });
+        let cmd = Parser::new("do { x = 1; } while x < 3;").parse_cmd().unwrap();
+        assert!(matches!(cmd, Cmd::DoWhile { ref body, .. } if body.len() == 1));
+        assert_eq!(Parser::new("break;").parse_cmd(), Err("'break' fora de um laço".into()));
+        // Uma variável que começa com a palavra-chave continua sendo uma atribuição
+        assert!(matches!(Parser::new("dobro = 1;").parse_cmd(), Ok(Cmd::Atrib { .. })));
+    }
+
+    // Testa parâmetros com valor padrão e argumentos nomeados
+    #[test]
+    fn test_parse_argumentos_nomeados() {
+        let prog = Parser::new("fun range(lo, hi, passo = 1) { return hi - lo; } main { return 0; }").parse_programa().unwrap();
+        assert_eq!(prog.funcoes[0].padroes.get("passo"), Some(&Expr::Const(1)));
+        let mut parser = Parser::new("range(0,\n  10, passo = 2)");
+        let args = vec![Expr::Const(0), Expr::Const(10)];
+        let nomeados = vec![("passo".to_string(), Expr::Const(2))];
+        let span = Span { inicio: (1, 1), fim: (2, 16) };
+        assert_eq!(parser.parse_expr(), Ok(Expr::Call { nome: "range".into(), args, nomeados, span }));
+        assert_eq!(span.to_string(), "1:1-2:16");
+        // A comparação continua sendo um argumento posicional
+        assert!(matches!(Parser::new("f(x == 1)").parse_expr(), Ok(Expr::Call { ref nomeados, .. }) if nomeados.is_empty()));
+        assert!(Parser::new("f(a = 1, 2)").parse_expr().is_err());
+        assert!(Parser::new("fun f(a = 1, b) { return b; } main { return 0; }").parse_programa().is_err());
+    }
+
+    // Testa procedimentos, retornos sem valor e chamadas usadas como comandos
+    #[test]
+    fn test_parse_procedimentos() {
+        let code = "fun mostra(x) { if x < 0 { return; } else { } print(x); } main { mostra(3); return 0; }";
+        let prog = Parser::new(code).parse_programa().unwrap();
+        assert_eq!(prog.funcoes[0].retorno, None);
+        assert!(matches!(prog.funcoes[0].comandos[0], Cmd::If { ref then_cmds, .. } if then_cmds == &[Cmd::Return(None)]));
+        let span = Span { inicio: (1, 66), fim: (1, 74) };
+        let chamada = Expr::Call { nome: "mostra".into(), args: vec![Expr::Const(3)], nomeados: vec![], span };
+        assert_eq!(prog.principal, vec![Cmd::Expr(chamada)]);
+        let fim = Parser::new("fun f() { print(1); return; } main { return 0; }").parse_programa().unwrap();
+        assert_eq!(fim.funcoes[0].retorno, None);
+    }
+
+    // Testa o bloco asm com as ligações dos operandos
+    #[test]
+    fn test_parse_asm() {
+        let mut parser = Parser::new(r#"asm { "mov rdi, {c}", "syscall", c = x, }"#);
+        let linhas = vec!["mov rdi, {c}".to_string(), "syscall".to_string()];
+        assert_eq!(parser.parse_cmd(), Ok(Cmd::Asm { linhas, operandos: vec![("c".into(), "x".into())] }));
+        // Chaves que não nomeiam um operando ligado ficam como estão
+        let mascara = Parser::new(r#"asm { "vaddpd zmm0{k1}{z}, zmm1, {v}", v = x }"#).parse_cmd();
+        let linhas = vec!["vaddpd zmm0{k1}{z}, zmm1, {v}".to_string()];
+        assert_eq!(mascara, Ok(Cmd::Asm { linhas, operandos: vec![("v".into(), "x".into())] }));
+        assert!(Parser::new(r#"asm { "nop", a = x }"#).parse_cmd().is_err());
+        assert!(Parser::new(r#"asm { "mov {a}, {a}", a = x, a = y }"#).parse_cmd().is_err());
+    }
+
+    // Testa os valores atribuídos às variantes dos enums
+    #[test]
+    fn test_parse_enum() {
+        let prog = Parser::new("enum Cor { Vermelho, Verde, Azul } main { return Azul; }").parse_programa().unwrap();
+        let variantes = vec![("Vermelho".to_string(), 0), ("Verde".to_string(), 1), ("Azul".to_string(), 2)];
+        assert_eq!(prog.enums, vec![EnumDecl { nome: "Cor".into(), variantes }]);
+        assert_eq!(prog.constantes[2], ("Azul".to_string(), Expr::Const(2)));
+        assert!(Parser::new("enum Cor { } main { return 0; }").parse_programa().is_err());
+        assert!(Parser::new("enum Cor { A, A } main { return 0; }").parse_programa().is_err());
+    }
+
+    // Testa o parsing do if como expressão, inclusive encadeado com else if
+    #[test]
+    fn test_parse_condicional() {
+        let condicional = |cond: Expr, entao: Expr, senao: Expr| Expr::Condicional {
+            cond: Box::new(cond),
+            entao: Box::new(entao),
+            senao: Box::new(senao),
+        };
+        let expr = Parser::new("if a { 1 } else if b { 2 } else { 3 }").parse_expr().unwrap();
+        let interno = condicional(Expr::Var("b".into()), Expr::Const(2), Expr::Const(3));
+        assert_eq!(expr, condicional(Expr::Var("a".into()), Expr::Const(1), interno));
+        // No início de um comando, o if continua sendo o comando condicional
+        let cmd = Parser::new("if c { x = if c { 1 } else { 2 } + 1; } else { }").parse_cmd().unwrap();
+        assert!(matches!(cmd, Cmd::If { ref then_cmds, .. } if matches!(&then_cmds[0], Cmd::Atrib { expr: Expr::OpBin { .. }, .. })));
+        assert!(Parser::new("if a { 1 }").parse_expr().is_err());
+    }
+
+    // Testa o parsing das atribuições compostas e dos incrementos
+    #[test]
+    fn test_parse_atrib_composta() {
+        let composta = |alvo: Expr, operador: &str, expr: Expr| Cmd::AtribComposta { alvo, operador: operador.into(), expr };
+        assert_eq!(Parser::new("x += 2;").parse_cmd(), Ok(composta(Expr::Var("x".into()), "+", Expr::Const(2))));
+        assert_eq!(Parser::new("x--;").parse_cmd(), Ok(composta(Expr::Var("x".into()), "-", Expr::Const(1))));
+        let elemento = Expr::Indice { nome: "v".into(), indice: Box::new(Expr::Const(3)) };
+        assert_eq!(Parser::new("v[3] %= 7;").parse_cmd(), Ok(composta(elemento, "%", Expr::Const(7))));
+        let cmd = Parser::new("*p *= k;").parse_cmd().unwrap();
+        assert_eq!(cmd, composta(Expr::Deref(Box::new(Expr::Var("p".into()))), "*", Expr::Var("k".into())));
+        assert!(matches!(Parser::new("p.x++;").parse_cmd(), Ok(Cmd::AtribComposta { .. })));
+        // Atribuições simples continuam com seus próprios comandos
+        assert!(matches!(Parser::new("v[1] = 2;").parse_cmd(), Ok(Cmd::AtribIndice { .. })));
+        assert!(matches!(Parser::new("x = x % 3;").parse_cmd(), Ok(Cmd::Atrib { expr: Expr::OpBin { ref operador, .. }, .. }) if operador == "%"));
+    }
+
+    // Testa o parsing de acesso e atribuição a elementos de vetor
+    #[test]
+    fn test_parse_indice() {
+        let mut parser = Parser::new("v[i + 1] = v[0];");
+        let cmd = parser.parse_cmd().unwrap();
+        // Verifica o índice da atribuição e a leitura do elemento 0
+        assert_eq!(
+            cmd,
+            Cmd::AtribIndice {
+                nome: "v".into(),
+                indice: Expr::OpBin {
+                    operador: "+".into(),
+                    esq: Box::new(Expr::Var("i".into())),
+                    dir: Box::new(Expr::Const(1)),
+                },
+                expr: Expr::Indice { nome: "v".into(), indice: Box::new(Expr::Const(0)) },
+            }
+        );
+    }
+
+    // Testa a declaração de vetores globais e locais
+    #[test]
+    fn test_parse_decl_vetor() {
+        let mut parser = Parser::new("var g[3]; fun f() { var v[10]; return v[0]; } main { return 0; }");
+        let prog = parser.parse_programa().unwrap();
+        assert_eq!(prog.vetores, vec![("g".to_string(), 3)]);
+        assert_eq!(prog.funcoes[0].vetores, vec![("v".to_string(), 10)]);
+        // Vetores de tamanho zero são rejeitados
+        let mut parser = Parser::new("var g[0]; main { return 0; }");
+        assert!(parser.parse_programa().is_err());
+    }
+
+    // Testa o parsing de um literal de string com escapes
+    #[test]
+    fn test_parse_string() {
+        let mut parser = Parser::new(r#""ola,  mundo\n\"x\"""#);
+        let expr = parser.parse_expr().unwrap();
+        // Os espaços internos são preservados e os escapes convertidos
+        assert_eq!(expr, Expr::Str("ola,  mundo\n\"x\"".into()));
+        assert!(Parser::new("\"aberta").parse_expr().is_err());
+    }
+
+    // Testa o parsing dos comandos print e println
+    #[test]
+    fn test_parse_print() {
+        let mut parser = Parser::new(r#"println("x = ", x);"#);
+        let cmd = parser.parse_cmd().unwrap();
+        assert_eq!(
+            cmd,
+            Cmd::Print {
+                args: vec![Expr::Str("x = ".into()), Expr::Var("x".into())],
+                tipos: vec![Tipo::I64, Tipo::I64],
+                nova_linha: true,
+            }
+        );
+    }
+
+    // Testa o parsing do builtin read
+    #[test]
+    fn test_parse_read() {
+        let mut parser = Parser::new("read() + 1");
+        let expr = parser.parse_expr().unwrap();
+        assert_eq!(
+            expr,
+            Expr::OpBin {
+                operador: "+".into(),
+                esq: Box::new(Expr::Read),
+                dir: Box::new(Expr::Const(1)),
+            }
+        );
+        // read não recebe argumentos
+        assert!(Parser::new("read(1)").parse_expr().is_err());
+    }
+
+    // Testa a declaração, construção e uso de registros
+    #[test]
+    fn test_parse_registro() {
+        let code = "struct Point { x, y } var p = Point { y: 2 }; main { p.x = p.y; return p.x; }";
+        let prog = Parser::new(code).parse_programa().unwrap();
+        assert_eq!(prog.registros, vec![RegistroDecl { nome: "Point".into(), campos: vec!["x".into(), "y".into()] }]);
+        // A construção só precisa informar parte dos campos
+        assert_eq!(
+            prog.globais[0].1,
+            Expr::Registro { tipo: "Point".into(), campos: vec![("y".into(), Expr::Const(2))] }
+        );
+        assert_eq!(
+            prog.principal[0],
+            Cmd::AtribCampo {
+                nome: "p".into(),
+                campo: "x".into(),
+                expr: Expr::Campo { nome: "p".into(), campo: "y".into() },
+            }
+        );
+        // Campos desconhecidos são rejeitados na construção
+        let code = "struct Point { x, y } var p = Point { z: 1 }; main { return 0; }";
+        assert!(Parser::new(code).parse_programa().is_err());
+    }
+
+    // Testa o parsing de endereços e desreferências
+    #[test]
+    fn test_parse_ponteiros() {
+        let mut parser = Parser::new("*(p + 8) = *p * 2;");
+        let cmd = parser.parse_cmd().unwrap();
+        // O '*' prefixo é desreferência; o do meio continua sendo multiplicação
+        assert_eq!(
+            cmd,
+            Cmd::AtribDeref {
+                ptr: Expr::OpBin {
+                    operador: "+".into(),
+                    esq: Box::new(Expr::Var("p".into())),
+                    dir: Box::new(Expr::Const(8)),
+                },
+                expr: Expr::OpBin {
+                    operador: "*".into(),
+                    esq: Box::new(Expr::Deref(Box::new(Expr::Var("p".into())))),
+                    dir: Box::new(Expr::Const(2)),
+                },
+            }
+        );
+        let expr = Parser::new("&v[1]").parse_expr().unwrap();
+        assert_eq!(
+            expr,
+            Expr::Endereco(Box::new(Expr::Indice { nome: "v".into(), indice: Box::new(Expr::Const(1)) }))
+        );
+        // Não há endereço de valores temporários
+        assert!(Parser::new("&(x + 1)").parse_expr().is_err());
+    }
+
+    // Testa o parsing de alloc e free
+    #[test]
+    fn test_parse_alloc_free() {
+        let mut parser = Parser::new("p = alloc(8 * n); free(p);");
+        assert_eq!(
+            parser.parse_cmd().unwrap(),
+            Cmd::Atrib {
+                nome: "p".into(),
+                expr: Expr::Alloc(Box::new(Expr::OpBin {
+                    operador: "*".into(),
+                    esq: Box::new(Expr::Const(8)),
+                    dir: Box::new(Expr::Var("n".into())),
+                })),
+            }
+        );
+        assert_eq!(parser.parse_cmd().unwrap(), Cmd::Free(Expr::Var("p".into())));
+    }
+
+    // Testa o parsing de funções aninhadas
+    #[test]
+    fn test_parse_aninhada() {
+        let code = "fun f(n) { var s = 0; fun soma(x) { s = s + x; return s; } var t = 1; return soma(n); } main { return f(1); }";
+        let prog = Parser::new(code).parse_programa().unwrap();
+        let f = &prog.funcoes[0];
+        // Variáveis podem vir antes ou depois das funções aninhadas
+        assert_eq!(f.variaveis.len(), 2);
+        assert_eq!(f.aninhadas.len(), 1);
+        assert_eq!(f.aninhadas[0].nome, "soma");
+        assert_eq!(f.aninhadas[0].parametros, vec!["x".to_string()]);
+    }
+
+    // Testa as anotações de tipo e os operadores de deslocamento
+    #[test]
+    fn test_parse_tipos() {
+        let code = "var h: u64 = 1 << 4 + 1; fun f(a: u64, b): u64 { var x: i64 = a >> b; return x; } main { return 0; }";
+        let prog = Parser::new(code).parse_programa().unwrap();
+        assert_eq!(prog.tipos["h"], Tipo::U64);
+        // "+" tem precedência maior que "<<"
+        match &prog.globais[0].1 {
+            Expr::OpBin { operador, dir, .. } => {
+                assert_eq!(operador, "<<");
+                assert!(matches!(dir.as_ref(), Expr::OpBin { operador, .. } if operador == "+"));
+            }
+            expr => panic!("Esperado deslocamento: {:?}", expr),
+        }
+        let f = &prog.funcoes[0];
+        assert_eq!(f.tipos.get("a"), Some(&Tipo::U64));
+        assert_eq!(f.tipos.get("b"), None);
+        assert_eq!(f.tipos["x"], Tipo::I64);
+        assert_eq!(f.tipo_retorno, Tipo::U64);
+        assert!(Parser::new("var x: u32 = 0; main { return 0; }").parse_programa().is_err());
+    }
+
+    // Testa o parsing do comando match
+    #[test]
+    fn test_parse_match() {
+        let mut parser = Parser::new("match x + 1 { 1 => { x = 2; } 2 | N => { } _ => { x = 0; } }");
+        let cmd = parser.parse_cmd().unwrap();
+        let atrib = |valor| Cmd::Atrib { nome: "x".into(), expr: Expr::Const(valor) };
+        match cmd {
+            Cmd::Match { bracos, padrao, .. } => {
+                assert_eq!(bracos.len(), 2);
+                assert_eq!(bracos[0], (vec![Expr::Const(1)], vec![atrib(2)]));
+                assert_eq!(bracos[1].0, vec![Expr::Const(2), Expr::Var("N".into())]);
+                assert_eq!(padrao, Some(vec![atrib(0)]));
+            }
+            cmd => panic!("Esperado match: {:?}", cmd),
+        }
+        let sem_padrao = Parser::new("match x { 1 => { } }").parse_cmd().unwrap();
+        assert!(matches!(sem_padrao, Cmd::Match { padrao: None, .. }));
+        assert!(Parser::new("match x { _ => { } 1 => { } }").parse_cmd().is_err());
+    }
+
+    // Testa o parsing de funções externas
+    #[test]
+    fn test_parse_extern() {
+        let code = "extern fun puts(s); extern fun printf(fmt, ...); extern fun rand(); main { return 0; }";
+        let prog = Parser::new(code).parse_programa().unwrap();
+        let puts = ExternDecl { nome: "puts".into(), parametros: vec!["s".into()], variadica: false };
+        assert_eq!(prog.externas[0], puts);
+        assert!(prog.externas[1].variadica);
+        assert!(prog.externas[2].parametros.is_empty());
+        let muitos = "extern fun f(a, b, c, d, e, f, g); main { return 0; }";
+        assert!(Parser::new(muitos).parse_programa().is_err());
+    }
+
+    // Testa o parsing de uma biblioteca: funções públicas e nenhum main
+    #[test]
+    fn test_parse_biblioteca() {
+        let prog = Parser::new("pub fun soma(a, b) { return a + b; } fun aux() { return 1; }").parse_programa().unwrap();
+        assert!(!prog.tem_principal);
+        assert!(prog.funcoes[0].publica);
+        assert!(!prog.funcoes[1].publica);
+        assert!(Parser::new("pub var x = 1;").parse_programa().is_err());
+    }
+
+    // Testa a linha e o texto da condição guardados pelo assert
+    #[test]
+    fn test_parse_assert() {
+        let code = "main {\n  assert(x >  0);\n\n  assert(\n    soma(1, 2) == 3, \"soma errada\");\n  return 0;\n}";
+        let prog = Parser::new(code).parse_programa().unwrap();
+        assert_eq!(
+            prog.principal[0],
+            Cmd::Assert {
+                cond: Expr::OpBin { operador: ">".into(), esq: Box::new(Expr::Var("x".into())), dir: Box::new(Expr::Const(0)) },
+                mensagem: None,
+                linha: 2,
+                texto: "x > 0".into(),
+            }
+        );
+        match &prog.principal[1] {
+            Cmd::Assert { mensagem, linha, texto, .. } => {
+                assert_eq!(mensagem.as_deref(), Some("soma errada"));
+                assert_eq!(*linha, 5);
+                assert_eq!(texto, "soma(1, 2) == 3");
+            }
+            cmd => panic!("Esperado assert: {:?}", cmd),
+        }
+    }
+
+    // Testa o retorno de tuplas e a desestruturação em variáveis locais
+    #[test]
+    fn test_parse_tuplas() {
+        let code = "fun divmod(a, b) { return (a / b, a - a / b * b); } fun f() { var (q, r: u64) = divmod(7, 2); return q + r; } main { return f(); }";
+        let prog = Parser::new(code).parse_programa().unwrap();
+        assert!(matches!(&prog.funcoes[0].retorno, Some(Expr::Tupla(valores)) if valores.len() == 2));
+        let f = &prog.funcoes[1];
+        let span = Span { inicio: (1, 81), fim: (1, 92) };
+        let chamada = Expr::Call { nome: "divmod".into(), args: vec![Expr::Const(7), Expr::Const(2)], nomeados: vec![], span };
+        let desestruturacao = Expr::Desestruturacao { nomes: vec!["q".into(), "r".into()], chamada: Box::new(chamada) };
+        assert_eq!(f.variaveis, vec![("q".into(), desestruturacao), ("r".into(), Expr::Componente(1))]);
+        assert_eq!(f.tipos.get("r"), Some(&Tipo::U64));
+        // Parênteses sem vírgula continuam agrupando
+        assert_eq!(Parser::new("(1)").parse_expr(), Ok(Expr::Const(1)));
+        assert!(Parser::new("var (a, b) = f(); main { return 0; }").parse_programa().is_err());
+    }
 }
@@ -0,0 +1,304 @@
+// Rotinas de suporte emitidas junto com o código gerado.
+// Todas recebem o argumento em rax, devolvem o resultado em rax e podem alterar
+// rcx, rdx, rsi, rdi, r8, r9 e r11.
+
+// Rotinas disponíveis no runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rotina {
+    PrintInt,   // Escreve um inteiro com sinal em decimal
+    PrintUint,  // Escreve um inteiro sem sinal em decimal
+    PrintStr,   // Escreve uma string terminada em zero
+    NovaLinha,  // Escreve uma quebra de linha
+    Read,       // Lê um inteiro com sinal da entrada padrão
+    Alloc,      // Reserva um bloco de memória no heap
+    Free,       // Devolve um bloco à lista de blocos livres
+    Descarrega, // Esvazia os buffers de saída da libc antes de uma escrita direta
+}
+
+// Retorna o código assembly de uma rotina do runtime
+pub fn gerar_rotina(rotina: Rotina) -> &'static str {
+    match rotina {
+        Rotina::PrintInt => PRINT_INT,
+        Rotina::PrintUint => PRINT_UINT,
+        Rotina::PrintStr => PRINT_STR,
+        Rotina::NovaLinha => PRINT_NL,
+        Rotina::Read => READ,
+        Rotina::Alloc => ALLOC,
+        Rotina::Free => FREE,
+        Rotina::Descarrega => DESCARREGA,
+    }
+}
+
+// Retorna as variáveis da seção .bss usadas pela rotina, se houver
+pub fn dados(rotina: Rotina) -> Option<&'static str> {
+    match rotina {
+        Rotina::Alloc | Rotina::Free => Some(HEAP),
+        _ => None,
+    }
+}
+
+// Retorna o label usado para chamar a rotina
+pub fn label(rotina: Rotina) -> &'static str {
+    match rotina {
+        Rotina::PrintInt => "_rt_print_int",
+        Rotina::PrintUint => "_rt_print_uint",
+        Rotina::PrintStr => "_rt_print_str",
+        Rotina::NovaLinha => "_rt_print_nl",
+        Rotina::Read => "_rt_read",
+        Rotina::Alloc => "_rt_alloc",
+        Rotina::Free => "_rt_free",
+        Rotina::Descarrega => "_rt_descarrega",
+    }
+}
+
+// Converte rax para decimal num buffer na pilha (de trás para frente) e usa a syscall write
+const PRINT_INT: &str = "
+_rt_print_int:
+sub rsp, 32
+lea rsi, [rsp+32]
+mov rcx, rax
+test rax, rax
+jns .digitos
+neg rax
+.digitos:
+mov r8, 10
+.laco:
+xor rdx, rdx
+div r8
+add dl, 48
+dec rsi
+mov [rsi], dl
+test rax, rax
+jnz .laco
+test rcx, rcx
+jns .escreve
+dec rsi
+mov byte [rsi], 45
+.escreve:
+lea rdx, [rsp+32]
+sub rdx, rsi
+mov rax, 1
+mov rdi, 1
+syscall
+add rsp, 32
+ret
+";
+
+// Como _rt_print_int, mas lê rax como um inteiro sem sinal (valores u64)
+const PRINT_UINT: &str = "
+_rt_print_uint:
+sub rsp, 32
+lea rsi, [rsp+32]
+mov r8, 10
+.laco:
+xor rdx, rdx
+div r8
+add dl, 48
+dec rsi
+mov [rsi], dl
+test rax, rax
+jnz .laco
+lea rdx, [rsp+32]
+sub rdx, rsi
+mov rax, 1
+mov rdi, 1
+syscall
+add rsp, 32
+ret
+";
+
+// Calcula o tamanho da string (até o byte zero) e usa a syscall write
+const PRINT_STR: &str = "
+_rt_print_str:
+mov rsi, rax
+xor rdx, rdx
+.tamanho:
+cmp byte [rsi+rdx], 0
+je .escreve
+inc rdx
+jmp .tamanho
+.escreve:
+mov rax, 1
+mov rdi, 1
+syscall
+ret
+";
+
+// Escreve o caractere '\n' a partir da pilha
+const PRINT_NL: &str = "
+_rt_print_nl:
+push 10
+mov rsi, rsp
+mov rdx, 1
+mov rax, 1
+mov rdi, 1
+syscall
+add rsp, 8
+ret
+";
+
+// Lê byte a byte da entrada padrão: ignora espaços iniciais, aceita um sinal opcional
+// e acumula dígitos até o primeiro caractere que não seja dígito (que é consumido).
+// Em fim de entrada ou entrada inválida, sem nenhum dígito, o resultado é 0.
+const READ: &str = "
+_rt_read:
+sub rsp, 8
+xor r8, r8
+xor r9, r9
+.espaco:
+call .ler
+cmp rax, -1
+je .fim
+cmp rax, 32
+je .espaco
+cmp rax, 9
+jb .sinal
+cmp rax, 13
+jbe .espaco
+.sinal:
+cmp rax, 45
+jne .mais
+mov r9, 1
+call .ler
+jmp .digito
+.mais:
+cmp rax, 43
+jne .digito
+call .ler
+.digito:
+sub rax, 48
+cmp rax, 9
+ja .fim
+imul r8, r8, 10
+add r8, rax
+call .ler
+jmp .digito
+.fim:
+mov rax, r8
+test r9, r9
+jz .sai
+neg rax
+.sai:
+add rsp, 8
+ret
+.ler:
+xor rax, rax
+xor rdi, rdi
+lea rsi, [rsp+8]
+mov rdx, 1
+syscall
+cmp rax, 1
+jne .eof
+movzx rax, byte [rsp+8]
+ret
+.eof:
+mov rax, -1
+ret
+";
+
+// Estado do heap: início e fim da área livre do trecho atual e lista de blocos livres
+const HEAP: &str = "_rt_heap_topo: resq 1
+_rt_heap_limite: resq 1
+_rt_heap_livre: resq 1
+";
+
+// Cada bloco tem um cabeçalho de 8 bytes com seu tamanho, seguido da área devolvida.
+// Procura na lista de livres o primeiro bloco grande o suficiente; se não houver,
+// corta o bloco do trecho atual, obtendo um novo trecho com mmap quando ele acaba.
+// Usa mmap em vez de brk para não disputar o fim do segmento de dados com o malloc
+// da libc quando o programa é ligado com código C. Devolve 0 quando não há memória.
+const ALLOC: &str = "
+_rt_alloc:
+add rax, 7
+and rax, -8
+jnz .busca
+mov rax, 8
+.busca:
+lea rdx, [_rt_heap_livre]
+.proximo:
+mov rcx, [rdx]
+test rcx, rcx
+jz .novo
+cmp [rcx], rax
+jae .reusa
+lea rdx, [rcx+8]
+jmp .proximo
+.reusa:
+mov r8, [rcx+8]
+mov [rdx], r8
+lea rax, [rcx+8]
+ret
+.novo:
+mov r8, rax
+mov rsi, [_rt_heap_topo]
+lea rdi, [rsi+r8+8]
+test rsi, rsi
+jz .mapeia
+cmp rdi, [_rt_heap_limite]
+jbe .corta
+.mapeia:
+lea rsi, [r8+4103]
+and rsi, -4096
+cmp rsi, 65536
+jae .trecho
+mov rsi, 65536
+.trecho:
+push r10
+push r8
+mov rax, 9
+xor rdi, rdi
+mov rdx, 3
+mov r10, 34
+mov r8, -1
+xor r9, r9
+syscall
+pop r8
+pop r10
+cmp rax, -4096
+ja .falha
+add rsi, rax
+mov [_rt_heap_limite], rsi
+mov rsi, rax
+lea rdi, [rsi+r8+8]
+.corta:
+mov [_rt_heap_topo], rdi
+mov [rsi], r8
+lea rax, [rsi+8]
+ret
+.falha:
+xor rax, rax
+ret
+";
+
+// Coloca o bloco no início da lista de livres; o próximo livre fica no
+// primeiro quadword da área do bloco. Ponteiros nulos são ignorados.
+const FREE: &str = "
+_rt_free:
+test rax, rax
+jz .fim
+lea rcx, [rax-8]
+mov rdx, [_rt_heap_livre]
+mov [rax], rdx
+mov [_rt_heap_livre], rcx
+.fim:
+ret
+";
+
+// Chama fflush(NULL) para que a saída já escrita com printf e outras funções da libc
+// saia antes da escrita feita com a syscall write. Segue o System V AMD64 ABI como as
+// chamadas de funções externas; preserva rax, que guarda o valor a ser escrito, e r10.
+const DESCARREGA: &str = "
+_rt_descarrega:
+push rax
+push r10
+push r12
+mov r12, rsp
+and rsp, -16
+xor edi, edi
+call fflush wrt ..plt
+mov rsp, r12
+pop r12
+pop r10
+pop rax
+ret
+";
@@ -0,0 +1,333 @@
+// Importa os tipos definidos no módulo de parser
+use crate::parser::{Cmd, Expr, FunDecl, Programa, Tipo};
+// Importa o HashMap da biblioteca padrão
+use std::collections::HashMap;
+
+// Nomes declarados em uma função: tipos das variáveis e dos retornos das funções aninhadas
+struct Nivel {
+    variaveis: HashMap<String, Tipo>,
+    funcoes: HashMap<String, Tipo>,
+    retorno: Tipo, // Tipo de retorno da própria função
+}
+
+// Tipos dos nomes visíveis no ponto em que as expressões são tipadas
+struct Tipagem {
+    globais: HashMap<String, Tipo>, // Variáveis e vetores globais
+    constantes: HashMap<String, Tipo>, // Constantes, com o tipo da expressão que as define
+    funcoes: HashMap<String, Tipo>, // Tipos de retorno das funções de nível superior
+    niveis: Vec<Nivel>,             // Funções que envolvem o ponto atual, a mais interna por último
+}
+
+// Calcula o tipo das expressões do programa e troca os operadores que dependem do
+// sinal pelas versões sem sinal ("u/", "u<", "u>" e "u>>") quando operam sobre u64.
+// Como em C, uma operação entre i64 e u64 é feita sem sinal. Literais acima de i64::MAX
+// e literais usados onde se espera um u64 (atribuições, inicializações e retornos) são u64.
+pub fn anotar(prog: &mut Programa) {
+    let mut tipagem = Tipagem {
+        globais: prog
+            .globais
+            .iter()
+            .map(|(nome, _)| nome)
+            .chain(prog.vetores.iter().map(|(nome, _)| nome))
+            .map(|nome| (nome.clone(), prog.tipos.get(nome).copied().unwrap_or_default()))
+            .collect(),
+        funcoes: prog.funcoes.iter().map(|f| (f.nome.clone(), f.tipo_retorno)).collect(),
+        constantes: HashMap::new(),
+        niveis: Vec::new(),
+    };
+    for (nome, expr) in &mut prog.constantes {
+        let tipo = tipagem.expr(expr);
+        tipagem.constantes.insert(nome.clone(), tipo);
+    }
+    for (nome, expr) in &mut prog.globais {
+        let tipo = prog.tipos.get(nome).copied().unwrap_or_default();
+        tipagem.expr_em(expr, tipo);
+    }
+    for func in &mut prog.funcoes {
+        tipagem.funcao(func);
+    }
+    for cmd in &mut prog.principal {
+        tipagem.cmd(cmd);
+    }
+    tipagem.expr(&mut prog.retorno);
+}
+
+impl Tipagem {
+    fn funcao(&mut self, func: &mut FunDecl) {
+        let nomes = func
+            .parametros
+            .iter()
+            .chain(func.variaveis.iter().map(|(nome, _)| nome))
+            .chain(func.vetores.iter().map(|(nome, _)| nome));
+        let variaveis = nomes
+            .map(|nome| (nome.clone(), func.tipos.get(nome).copied().unwrap_or_default()))
+            .collect();
+        let funcoes = func.aninhadas.iter().map(|g| (g.nome.clone(), g.tipo_retorno)).collect();
+        self.niveis.push(Nivel { variaveis, funcoes, retorno: func.tipo_retorno });
+
+        for (nome, expr) in func.padroes.iter_mut().chain(func.variaveis.iter_mut().map(|(nome, expr)| (&*nome, expr))) {
+            let tipo = func.tipos.get(nome).copied().unwrap_or_default();
+            self.expr_em(expr, tipo);
+        }
+        for filha in &mut func.aninhadas {
+            self.funcao(filha);
+        }
+        for cmd in &mut func.comandos {
+            self.cmd(cmd);
+        }
+        if let Some(retorno) = &mut func.retorno {
+            self.expr_em(retorno, func.tipo_retorno);
+        }
+        self.niveis.pop();
+    }
+
+    // Tipo de uma variável: locais escondem as das funções externas, as globais e as constantes
+    fn variavel(&self, nome: &str) -> Option<Tipo> {
+        self.niveis
+            .iter()
+            .rev()
+            .find_map(|nivel| nivel.variaveis.get(nome))
+            .or_else(|| self.globais.get(nome))
+            .or_else(|| self.constantes.get(nome))
+            .copied()
+    }
+
+    // Tipo de retorno da função atual; o bloco principal devolve i64
+    fn retorno(&self) -> Tipo {
+        self.niveis.last().map(|nivel| nivel.retorno).unwrap_or_default()
+    }
+
+    // Tipo do valor devolvido por uma chamada; chamadas indiretas devolvem i64
+    fn chamada(&self, nome: &str) -> Tipo {
+        if self.variavel(nome).is_some() {
+            return Tipo::I64;
+        }
+        self.niveis
+            .iter()
+            .rev()
+            .find_map(|nivel| nivel.funcoes.get(nome))
+            .or_else(|| self.funcoes.get(nome))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn cmd(&mut self, cmd: &mut Cmd) {
+        match cmd {
+            Cmd::If { cond, then_cmds, else_cmds } => {
+                self.expr(cond);
+                for filho in then_cmds.iter_mut().chain(else_cmds) {
+                    self.cmd(filho);
+                }
+            }
+            Cmd::While { cond, body } | Cmd::DoWhile { body, cond } => {
+                self.expr(cond);
+                for filho in body {
+                    self.cmd(filho);
+                }
+            }
+            Cmd::Loop { body } => {
+                for filho in body {
+                    self.cmd(filho);
+                }
+            }
+            Cmd::Break | Cmd::Asm { .. } | Cmd::Return(None) => {}
+            Cmd::Atrib { nome, expr } => {
+                let tipo = self.variavel(nome).unwrap_or_default();
+                self.expr_em(expr, tipo);
+            }
+            Cmd::Return(Some(expr)) => {
+                let tipo = self.retorno();
+                self.expr_em(expr, tipo);
+            }
+            Cmd::AtribCampo { expr, .. } | Cmd::Free(expr) | Cmd::Assert { cond: expr, .. } | Cmd::Expr(expr) => {
+                self.expr(expr);
+            }
+            Cmd::AtribIndice { indice, expr, .. } => {
+                self.expr(indice);
+                self.expr(expr);
+            }
+            // A divisão e o resto no lugar seguem as mesmas regras dos operadores
+            Cmd::AtribComposta { alvo, operador, expr } => {
+                let tipo_alvo = self.expr(alvo);
+                let sem_sinal = tipo_alvo == Tipo::U64 || self.expr_em(expr, tipo_alvo) == Tipo::U64;
+                if sem_sinal && (operador == "/" || operador == "%") {
+                    *operador = format!("u{}", operador);
+                }
+            }
+            Cmd::AtribDeref { ptr, expr } => {
+                self.expr(ptr);
+                self.expr(expr);
+            }
+            // Valores u64 são escritos sem sinal
+            Cmd::Print { args, tipos, .. } => {
+                for (arg, tipo) in args.iter_mut().zip(tipos) {
+                    *tipo = self.expr(arg);
+                }
+            }
+            // Os padrões são comparados com o valor, então seus literais seguem o tipo dele
+            Cmd::Match { valor, bracos, padrao } => {
+                let tipo = self.expr(valor);
+                for padroes in bracos.iter_mut().map(|(padroes, _)| padroes) {
+                    for p in padroes {
+                        self.expr_em(p, tipo);
+                    }
+                }
+                for filho in bracos.iter_mut().flat_map(|(_, cmds)| cmds).chain(padrao.iter_mut().flatten()) {
+                    self.cmd(filho);
+                }
+            }
+        }
+    }
+
+    // Anota a expressão e devolve o seu tipo
+    fn expr(&mut self, expr: &mut Expr) -> Tipo {
+        self.expr_em(expr, Tipo::I64)
+    }
+
+    // Anota a expressão cujo valor será guardado como `contexto`: em um contexto u64,
+    // os literais das operações aritméticas e dos valores de um if também são u64
+    fn expr_em(&mut self, expr: &mut Expr, contexto: Tipo) -> Tipo {
+        match expr {
+            // Só literais acima de i64::MAX ficam negativos ao serem guardados em um i64
+            Expr::Const(n) if *n < 0 => Tipo::U64,
+            Expr::Const(_) => contexto,
+            Expr::Var(nome) => self.variavel(nome).unwrap_or_default(),
+            Expr::Call { nome, args, nomeados, .. } => {
+                for arg in args.iter_mut().chain(nomeados.iter_mut().map(|(_, arg)| arg)) {
+                    self.expr(arg);
+                }
+                self.chamada(nome)
+            }
+            Expr::OpBin { operador, esq, dir } => {
+                // Comparações e a quantidade de um deslocamento não recebem o contexto
+                let comparacao = matches!(operador.as_str(), "<" | ">" | "==");
+                let deslocamento = matches!(operador.as_str(), "<<" | ">>");
+                let tipo_esq = self.expr_em(esq, if comparacao { Tipo::I64 } else { contexto });
+                let tipo_dir = self.expr_em(dir, if comparacao || deslocamento { Tipo::I64 } else { contexto });
+                let sem_sinal = tipo_esq == Tipo::U64 || tipo_dir == Tipo::U64;
+                match operador.as_str() {
+                    // O deslocamento tem o tipo do operando da esquerda
+                    ">>" if tipo_esq == Tipo::U64 => {
+                        *operador = "u>>".into();
+                        tipo_esq
+                    }
+                    "<<" | ">>" => tipo_esq,
+                    // Comparações produzem 0 ou 1
+                    "<" | ">" if sem_sinal => {
+                        *operador = format!("u{}", operador);
+                        Tipo::I64
+                    }
+                    "<" | ">" | "==" => Tipo::I64,
+                    "/" | "%" if sem_sinal => {
+                        *operador = format!("u{}", operador);
+                        Tipo::U64
+                    }
+                    _ if sem_sinal => Tipo::U64,
+                    _ => Tipo::I64,
+                }
+            }
+            Expr::Indice { indice, .. } => {
+                self.expr(indice);
+                Tipo::I64
+            }
+            Expr::Registro { campos, .. } => {
+                for (_, valor) in campos {
+                    self.expr(valor);
+                }
+                Tipo::I64
+            }
+            Expr::Alloc(e) | Expr::Endereco(e) | Expr::Deref(e) | Expr::Desestruturacao { chamada: e, .. } => {
+                self.expr(e);
+                Tipo::I64
+            }
+            // Como nas operações, basta um dos valores ser u64 para o resultado ser u64
+            Expr::Condicional { cond, entao, senao } => {
+                self.expr(cond);
+                let tipo_entao = self.expr_em(entao, contexto);
+                let tipo_senao = self.expr_em(senao, contexto);
+                if tipo_entao == Tipo::U64 || tipo_senao == Tipo::U64 {
+                    Tipo::U64
+                } else {
+                    Tipo::I64
+                }
+            }
+            // Cada valor da tupla é tipado separadamente; as variáveis que os recebem têm seus próprios tipos
+            Expr::Tupla(valores) => {
+                for valor in valores {
+                    self.expr(valor);
+                }
+                Tipo::I64
+            }
+            Expr::Str(_) | Expr::Read | Expr::Campo { .. } | Expr::Componente(_) => Tipo::I64,
+        }
+    }
+}
+
+//////////////
+/// TESTES ///
+//////////////
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    // Faz o parsing e a anotação de tipos de um programa
+    fn anotado(code: &str) -> Programa {
+        let mut prog = Parser::new(code).parse_programa().unwrap();
+        anotar(&mut prog);
+        prog
+    }
+
+    // Operador da expressão binária
+    fn operador(expr: &Expr) -> &str {
+        match expr {
+            Expr::OpBin { operador, .. } => operador,
+            _ => panic!("Esperada operação binária: {:?}", expr),
+        }
+    }
+
+    // Testa a escolha das operações sem sinal a partir dos tipos dos operandos
+    #[test]
+    fn test_operadores_sem_sinal() {
+        let prog = anotado("var h: u64 = 5; var s = 5; main { h = h / 2; s = s / 2; return h >> 1 > s >> 1; }");
+        let atribuido = |i: usize| match &prog.principal[i] {
+            Cmd::Atrib { expr, .. } => operador(expr).to_string(),
+            cmd => panic!("Esperada atribuição: {:?}", cmd),
+        };
+        assert_eq!(atribuido(0), "u/");
+        assert_eq!(atribuido(1), "/");
+        // A comparação entre u64 e i64 é sem sinal; o deslocamento segue o operando da esquerda
+        assert_eq!(operador(&prog.retorno), "u>");
+        match &prog.retorno {
+            Expr::OpBin { esq, dir, .. } => {
+                assert_eq!(operador(esq), "u>>");
+                assert_eq!(operador(dir), ">>");
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // Testa os tipos de parâmetros, retornos e variáveis que escondem outras
+    #[test]
+    fn test_tipos_funcoes() {
+        let code = "var x: u64 = 1; fun f(a: u64): u64 { return a; } fun g(x) { return x < 3; } main { return f(1) < 2; }";
+        let prog = anotado(code);
+        assert_eq!(operador(&prog.retorno), "u<");
+        // O parâmetro "x" de g é i64 e esconde a global u64
+        assert_eq!(operador(prog.funcoes[1].retorno.as_ref().unwrap()), "<");
+    }
+
+    // Testa os literais u64: acima de i64::MAX ou guardados em variáveis e retornos u64
+    #[test]
+    fn test_literais_sem_sinal() {
+        let code = "var h: u64 = 0xFFFF_FFFF_FFFF_FFFF >> 1; var g: u64 = 1 << 63 >> 1; var s = 1 << 63 >> 1; \
+                    fun f(): u64 { return 6 / 2; } main { return 0x8000_0000_0000_0000 / 2; }";
+        let prog = anotado(code);
+        let inicial = |i: usize| operador(&prog.globais[i].1).to_string();
+        assert_eq!(inicial(0), "u>>");
+        assert_eq!(inicial(1), "u>>");
+        assert_eq!(inicial(2), ">>");
+        assert_eq!(operador(prog.funcoes[0].retorno.as_ref().unwrap()), "u/");
+        assert_eq!(operador(&prog.retorno), "u/");
+    }
+}